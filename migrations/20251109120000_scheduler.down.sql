@@ -0,0 +1,3 @@
+ALTER TABLE card_state DROP COLUMN difficulty;
+ALTER TABLE card_state DROP COLUMN stability;
+ALTER TABLE decks DROP COLUMN scheduler;
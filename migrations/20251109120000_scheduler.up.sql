@@ -0,0 +1,5 @@
+ALTER TABLE decks ADD COLUMN scheduler TEXT NOT NULL DEFAULT 'sm2'; -- sm2, fsrs
+
+-- FSRS memory state, unused by sm2
+ALTER TABLE card_state ADD COLUMN stability  REAL NOT NULL DEFAULT 0;
+ALTER TABLE card_state ADD COLUMN difficulty REAL NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE decks ADD COLUMN scheduling TEXT NOT NULL DEFAULT '{}'; -- stored as JSON string
@@ -0,0 +1 @@
+ALTER TABLE review_log DROP COLUMN duration_ms;
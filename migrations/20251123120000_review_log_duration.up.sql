@@ -0,0 +1 @@
+ALTER TABLE review_log ADD COLUMN duration_ms INTEGER NOT NULL DEFAULT 0; -- time spent solving the card
@@ -0,0 +1,2 @@
+ALTER TABLE card_state DROP COLUMN buried_until_s;
+ALTER TABLE card_state DROP COLUMN prev_status;
@@ -0,0 +1,3 @@
+-- status to restore on unsuspend / unbury
+ALTER TABLE card_state ADD COLUMN prev_status    INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE card_state ADD COLUMN buried_until_s INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE review_log ADD COLUMN prev_status INTEGER NOT NULL DEFAULT 0; -- card status before the review
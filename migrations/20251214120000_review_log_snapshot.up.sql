@@ -0,0 +1 @@
+ALTER TABLE review_log ADD COLUMN prev_state TEXT; -- card_state before the review as JSON, used by undo
@@ -0,0 +1 @@
+ALTER TABLE card_state DROP COLUMN leech;
@@ -0,0 +1 @@
+ALTER TABLE card_state ADD COLUMN leech BOOL NOT NULL DEFAULT FALSE;
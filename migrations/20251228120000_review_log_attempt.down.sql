@@ -0,0 +1,2 @@
+ALTER TABLE review_log DROP COLUMN revealed;
+ALTER TABLE review_log DROP COLUMN commands;
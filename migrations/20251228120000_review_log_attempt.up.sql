@@ -0,0 +1,2 @@
+ALTER TABLE review_log ADD COLUMN commands INTEGER NOT NULL DEFAULT 0; -- commands entered before solving
+ALTER TABLE review_log ADD COLUMN revealed BOOL NOT NULL DEFAULT FALSE; -- answer shown without solving
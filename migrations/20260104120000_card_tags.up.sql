@@ -0,0 +1 @@
+ALTER TABLE cards ADD COLUMN tags TEXT NOT NULL DEFAULT '[]'; -- JSON array of strings
@@ -0,0 +1 @@
+ALTER TABLE card_state DROP COLUMN last_review_s;
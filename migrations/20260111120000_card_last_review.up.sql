@@ -0,0 +1,7 @@
+ALTER TABLE card_state ADD COLUMN last_review_s INTEGER; -- NULL until the card is reviewed
+UPDATE card_state
+SET last_review_s = (
+    SELECT MAX(timestamp)
+    FROM review_log
+    WHERE review_log.card_id = card_state.card_id AND review_log.rating > 0
+);
@@ -0,0 +1 @@
+ALTER TABLE decks DROP COLUMN finish_by_s;
@@ -0,0 +1 @@
+ALTER TABLE decks ADD COLUMN finish_by_s INTEGER; -- start of the last study day of the plan, NULL without one
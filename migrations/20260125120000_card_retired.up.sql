@@ -0,0 +1,2 @@
+-- removed from the deck file on an update import, kept for its review history
+ALTER TABLE cards ADD COLUMN retired BOOL NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+DROP INDEX idx_cards_deck_key;
+ALTER TABLE cards DROP COLUMN key;
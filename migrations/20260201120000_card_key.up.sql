@@ -0,0 +1,3 @@
+-- optional id given by the deck author, stable across reorders of the deck file
+ALTER TABLE cards ADD COLUMN key TEXT;
+CREATE UNIQUE INDEX idx_cards_deck_key ON cards(deck_name, key);
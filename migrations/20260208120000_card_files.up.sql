@@ -0,0 +1 @@
+ALTER TABLE cards ADD COLUMN files TEXT NOT NULL DEFAULT '[]'; -- JSON array of fixture files
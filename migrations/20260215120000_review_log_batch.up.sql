@@ -0,0 +1 @@
+ALTER TABLE review_log ADD COLUMN batch_id INTEGER; -- shared by entries saved together, which are undone together
@@ -0,0 +1 @@
+ALTER TABLE review_log ADD COLUMN suspended BOOLEAN NOT NULL DEFAULT FALSE; -- the review suspended the card as a leech
@@ -1,19 +1,177 @@
 use clap::{Parser, Subcommand};
 
+use crate::domain::{
+    card::CardRef,
+    card_state::CardStatus,
+    due_date::{DueDate, MAX_DAYS},
+};
+
 #[derive(Parser)]
 pub struct Args {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Pretend the current time is this unix timestamp, to simulate future sessions
+    #[arg(long, global = true, hide = true)]
+    pub now: Option<i64>,
+
+    /// Timezone for study days and dates, like Europe/Madrid. Defaults to the system one
+    #[arg(long, global = true, env = "TERMCARDS_TIMEZONE")]
+    pub timezone: Option<String>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     #[command(about = "Import a deck")]
-    Import { path: String },
+    Import {
+        #[arg(help = "Deck file, or .tcdeck package")]
+        path: String,
+        #[arg(
+            long,
+            help = "Update the deck if it was already imported, keeping the progress of its cards"
+        )]
+        update: bool,
+        #[arg(
+            long,
+            requires = "update",
+            help = "Retire the cards no longer in the file instead of keeping them"
+        )]
+        retire: bool,
+    },
     #[command(about = "Review a deck")]
-    Review { deck_name: String },
+    Review {
+        deck_name: String,
+        #[arg(
+            long,
+            value_parser = parse_days,
+            help = "Also review cards due in the next days, like 3d"
+        )]
+        ahead: Option<i64>,
+    },
     #[command(about = "Get the state of a deck")]
     State { deck_name: String },
+    #[command(about = "Show the review history of a deck")]
+    History {
+        deck_name: String,
+        #[arg(
+            long,
+            help = "Only show the card at this position in the deck, or with this id"
+        )]
+        card: Option<CardRef>,
+    },
+    #[command(about = "Show how many cards will be due each day")]
+    Forecast {
+        #[arg(help = "Deck name, or prefix of the deck names. All decks if omitted")]
+        deck_name: Option<String>,
+        #[arg(long, default_value_t = 30, value_parser = parse_days)]
+        days: i64,
+    },
+    #[command(about = "Undo the last rating")]
+    Undo,
+    #[command(about = "List cards that keep being forgotten")]
+    Leeches {
+        #[arg(help = "Deck name, or prefix of the deck names. All decks if omitted")]
+        deck_name: Option<String>,
+    },
+    #[command(about = "Fit the scheduler parameters to the review history")]
+    Optimize {
+        #[arg(help = "Deck name, or prefix of the deck names. All decks if omitted")]
+        deck_name: Option<String>,
+        #[arg(
+            long,
+            help = "Save the fitted parameters in the deck scheduling settings"
+        )]
+        write: bool,
+    },
+    #[command(about = "Stop a card from being reviewed")]
+    Suspend { deck_name: String, card: CardRef },
+    #[command(about = "Return a suspended or buried card to the review queue")]
+    Unsuspend { deck_name: String, card: CardRef },
+    #[command(about = "Hide a card until tomorrow")]
+    Bury { deck_name: String, card: CardRef },
+    #[command(about = "Run the cards of a deck regardless of when they are due")]
+    Cram {
+        deck_name: String,
+        #[arg(long, help = "Only cards with this tag")]
+        tag: Option<String>,
+        #[arg(
+            long,
+            help = "Only cards in this status: new, learn, review, relearn, suspended or buried"
+        )]
+        status: Option<CardStatus>,
+        #[arg(long, help = "Run the cards in random order")]
+        shuffle: bool,
+        #[arg(long, help = "Show failed cards again until they pass")]
+        requeue: bool,
+    },
+    #[command(
+        about = "Move the reviews due during an absence past it",
+        allow_missing_positional = true
+    )]
+    Postpone {
+        #[arg(required_unless_present = "all")]
+        deck_name: Option<String>,
+        #[arg(long, conflicts_with = "deck_name", help = "Postpone every deck")]
+        all: bool,
+        #[arg(value_parser = parse_days, help = "Length of the absence, like 7d")]
+        days: i64,
+    },
+    #[command(about = "Pace the new cards of a deck to introduce them all by a date")]
+    Plan {
+        deck_name: String,
+        #[arg(
+            long,
+            required_unless_present = "clear",
+            help = "Date as YYYY-MM-DD, or days from today as +Nd"
+        )]
+        finish_by: Option<DueDate>,
+        #[arg(
+            long,
+            conflicts_with = "finish_by",
+            help = "Go back to the deck daily limit"
+        )]
+        clear: bool,
+    },
+    #[command(about = "Change the state of a single card")]
+    Card {
+        #[command(subcommand)]
+        command: CardCommands,
+    },
+    #[command(about = "Build a .tcdeck package of an imported deck")]
+    ExportPackage {
+        deck_name: String,
+        #[arg(long, short, help = "Package path. Defaults to <deck>.tcdeck")]
+        output: Option<String>,
+    },
     #[command(about = "Test a deck without importing it")]
     TestDeck { path: String },
 }
+
+#[derive(Subcommand)]
+pub enum CardCommands {
+    #[command(about = "Reset a card to new, clearing its ease, reps and lapses")]
+    Forget { deck_name: String, card: CardRef },
+    #[command(about = "Move the next review of a card")]
+    SetDue {
+        deck_name: String,
+        card: CardRef,
+        #[arg(help = "Date as YYYY-MM-DD, or days from today as +Nd")]
+        due: DueDate,
+    },
+}
+
+/// Number of days, given as `3d` or `3`
+fn parse_days(s: &str) -> Result<i64, String> {
+    let days: i64 = s
+        .strip_suffix('d')
+        .unwrap_or(s)
+        .parse()
+        .map_err(|_| format!("expected a number of days like 3d, got {s}"))?;
+    if days < 0 {
+        return Err("days cannot be negative".to_string());
+    }
+    if days > MAX_DAYS {
+        return Err(format!("days cannot be more than {MAX_DAYS}"));
+    }
+    Ok(days)
+}
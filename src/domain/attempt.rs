@@ -0,0 +1,52 @@
+use super::{card_state::ReviewResult, scheduling::Scheduling};
+
+/// How a card went in the sandbox
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Attempt {
+    pub duration_ms: i64,
+    /// Lines entered in the shell before the expected output showed up
+    pub commands: i64,
+    /// The card was not solved and its answer was shown
+    pub revealed: bool,
+}
+
+impl Attempt {
+    /// Rating pre-selected on the review screen, or applied directly with `auto_grade`
+    pub fn suggested_rating(&self, scheduling: &Scheduling) -> ReviewResult {
+        let seconds = self.duration_ms / 1000;
+        if self.revealed {
+            ReviewResult::Again
+        } else if self.commands <= 1 && seconds < scheduling.easy_solve_seconds {
+            ReviewResult::Easy
+        } else if seconds < scheduling.good_solve_seconds {
+            ReviewResult::Good
+        } else {
+            ReviewResult::Hard
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::domain::{card_state::ReviewResult, scheduling::Scheduling};
+
+    use super::Attempt;
+
+    fn suggest(duration_ms: i64, commands: i64, revealed: bool) -> ReviewResult {
+        Attempt {
+            duration_ms,
+            commands,
+            revealed,
+        }
+        .suggested_rating(&Scheduling::default())
+    }
+
+    #[test]
+    fn test_suggested_rating() {
+        assert_eq!(suggest(5_000, 1, false), ReviewResult::Easy);
+        assert_eq!(suggest(5_000, 2, false), ReviewResult::Good);
+        assert_eq!(suggest(30_000, 1, false), ReviewResult::Good);
+        assert_eq!(suggest(90_000, 4, false), ReviewResult::Hard);
+        assert_eq!(suggest(5_000, 1, true), ReviewResult::Again);
+    }
+}
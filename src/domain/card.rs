@@ -1,9 +1,15 @@
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Card {
-    #[serde(default)]
+    #[serde(skip)]
     pub id: i64,
+    /// Id given by the deck author, written as `id` in the deck file. Not a number, so it can't be
+    /// taken for a position.
+    #[serde(default, rename = "id", skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
     pub volume_mounts: Vec<(String, String)>,
     pub expected_output: String,
     pub expected_input: String,
@@ -12,4 +18,55 @@ pub struct Card {
     pub work_dir: Option<String>,
     #[serde(default)]
     pub one_time: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Fixture files written to a temporary directory mounted at `work_dir` while the card runs
+    #[serde(default)]
+    pub files: Vec<CardFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CardFile {
+    /// Relative to `work_dir`
+    pub path: String,
+    pub content: String,
+    /// Unix permissions, like 0o755. Defaults to 0o644
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+}
+
+impl Card {
+    /// Whether both cards ask and check the same thing, whatever their ids
+    pub fn same_content(&self, other: &Card) -> bool {
+        self.key == other.key
+            && self.volume_mounts == other.volume_mounts
+            && self.expected_output == other.expected_output
+            && self.expected_input == other.expected_input
+            && self.command == other.command
+            && self.docker_image == other.docker_image
+            && self.work_dir == other.work_dir
+            && self.one_time == other.one_time
+            && self.tags == other.tags
+            && self.files == other.files
+    }
+}
+
+/// A card of a deck as given on the command line: its position, as shown by `termcards state`,
+/// or its key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CardRef {
+    Ord(i64),
+    Key(String),
+}
+
+impl FromStr for CardRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("expected a card position or id".to_string());
+        }
+        Ok(s.parse()
+            .map_or_else(|_| CardRef::Key(s.to_string()), CardRef::Ord))
+    }
 }
@@ -1,11 +1,13 @@
-use std::{
-    time::{SystemTime, UNIX_EPOCH},
-    usize,
-};
+use std::str::FromStr;
 
+use serde::{Deserialize, Serialize};
 use sqlx::{Type, prelude::FromRow};
 
-#[derive(Debug, FromRow, Clone)]
+use crate::utils::time_utils::days_between;
+
+use super::scheduler::Scheduler;
+
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize)]
 pub struct CardState {
     pub card_id: i64,
     pub next_review_s: i64,
@@ -18,29 +20,64 @@ pub struct CardState {
 
     pub status: CardStatus,
     pub learning_step: i64,
+
+    pub stability: f64,
+    pub difficulty: f64,
+
+    #[serde(default)]
+    pub leech: bool,
+    /// When the card was last rated, None if it never was
+    #[serde(default)]
+    pub last_review_s: Option<i64>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Copy, Type)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Type, Serialize, Deserialize)]
 #[repr(i64)]
 #[sqlx(type_name = "INTEGER")]
 pub enum CardStatus {
     OneTimeLearned = -3,
+    Buried = -2,
+    Suspended = -1,
     New = 0,
     Learn = 1,
     Review = 2,
+    /// Lapsed review card going through the relearning steps
+    Relearn = 3,
 }
 
 impl From<i64> for CardStatus {
     fn from(value: i64) -> Self {
         match value {
+            -3 => CardStatus::OneTimeLearned,
+            -2 => CardStatus::Buried,
+            -1 => CardStatus::Suspended,
             1 => CardStatus::Learn,
             2 => CardStatus::Review,
+            3 => CardStatus::Relearn,
             _ => CardStatus::New,
         }
     }
 }
 
-#[derive(PartialEq, Eq)]
+impl FromStr for CardStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "new" => Ok(CardStatus::New),
+            "learn" => Ok(CardStatus::Learn),
+            "review" => Ok(CardStatus::Review),
+            "relearn" => Ok(CardStatus::Relearn),
+            "suspended" => Ok(CardStatus::Suspended),
+            "buried" => Ok(CardStatus::Buried),
+            _ => Err(format!(
+                "unknown status `{s}`, expected new, learn, review, relearn, suspended or buried"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReviewResult {
     Again = 1,
     Hard = 2,
@@ -48,161 +85,183 @@ pub enum ReviewResult {
     Easy = 4,
 }
 
-const MIN: i64 = 60;
-const DAY: i64 = 24 * 60 * 60;
-const LEARNING_INTERVALS: [i64; 3] = [1 * MIN, 10 * MIN, 1 * DAY];
-const LAST_LEARNING_STEP: i64 = LEARNING_INTERVALS.len() as i64 - 1;
+impl ReviewResult {
+    pub fn from_rating(rating: i64) -> Option<ReviewResult> {
+        match rating {
+            1 => Some(ReviewResult::Again),
+            2 => Some(ReviewResult::Hard),
+            3 => Some(ReviewResult::Good),
+            4 => Some(ReviewResult::Easy),
+            _ => None,
+        }
+    }
+}
 
-const GRADUATING_DAYS: i64 = 1;
+impl CardState {
+    /// State of a card that has never been reviewed
+    pub fn new(card_id: i64, next_review_s: i64, ease: i64) -> CardState {
+        CardState {
+            card_id,
+            next_review_s,
+            interval_days: 1,
+            ease,
+            reps: 0,
+            lapses: 0,
+            status: CardStatus::New,
+            learning_step: 0,
+            stability: 0.0,
+            difficulty: 0.0,
+            leech: false,
+            last_review_s: None,
+        }
+    }
 
-const MIN_EASE: i64 = 1300;
-const MAX_EASE: i64 = 3500;
+    pub fn apply_review(
+        &mut self,
+        scheduler: &dyn Scheduler,
+        review_result: ReviewResult,
+        one_time: bool,
+        now_s: i64,
+    ) {
+        self.apply(scheduler, review_result, one_time, now_s, false);
+    }
 
-const EASY_MULT: f64 = 1.3;
-impl CardState {
-    pub fn apply_review(&mut self, review_result: ReviewResult, one_time: bool) {
-        self.learning_step = self.learning_step.clamp(0, LAST_LEARNING_STEP);
+    /// Like `apply_review`, for a card the review queue served ahead of its due day
+    pub fn apply_early_review(
+        &mut self,
+        scheduler: &dyn Scheduler,
+        review_result: ReviewResult,
+        one_time: bool,
+        now_s: i64,
+    ) {
+        self.apply(scheduler, review_result, one_time, now_s, true);
+    }
 
+    fn apply(
+        &mut self,
+        scheduler: &dyn Scheduler,
+        review_result: ReviewResult,
+        one_time: bool,
+        now_s: i64,
+        early: bool,
+    ) {
         self.reps += 1;
-        if ReviewResult::Again == review_result {
+        // Only forgetting a graduated card is a lapse, not failing a learning step
+        if self.status == CardStatus::Review && ReviewResult::Again == review_result {
             self.lapses += 1
         }
 
-        if one_time {
-            if review_result != ReviewResult::Again {
-                self.status = CardStatus::OneTimeLearned;
-            }
+        if one_time && review_result != ReviewResult::Again {
+            self.status = CardStatus::OneTimeLearned;
             return;
         }
 
-        let now_s = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        match self.status {
-            // Learning
-            CardStatus::New | CardStatus::Learn => {
-                self.status = CardStatus::Learn;
-                match review_result {
-                    ReviewResult::Again => {
-                        self.learning_step = 0;
-                        self.next_review_s = now_s + LEARNING_INTERVALS[0];
-                    }
-                    ReviewResult::Hard => {
-                        self.next_review_s =
-                            now_s + LEARNING_INTERVALS[self.learning_step as usize];
-                    }
-                    ReviewResult::Good => {
-                        self.learning_step += 1;
-                        if self.learning_step > LAST_LEARNING_STEP {
-                            self.status = CardStatus::Review;
-                            self.learning_step = 0;
-                            self.interval_days = GRADUATING_DAYS;
-                            self.next_review_s = now_s + GRADUATING_DAYS * DAY;
-                        } else {
-                            self.next_review_s =
-                                now_s + LEARNING_INTERVALS[self.learning_step as usize];
-                        }
-                    }
-                    ReviewResult::Easy => {
-                        self.next_review_s = now_s + 4 * 24 * 60 * 60;
-                        self.interval_days = 1;
-                        self.status = CardStatus::Review;
-                    }
-                }
+        if matches!(
+            self.status,
+            CardStatus::OneTimeLearned | CardStatus::Suspended | CardStatus::Buried
+        ) {
+            return;
+        }
 
+        match early {
+            true => scheduler.schedule_early(self, review_result, now_s),
+            false => scheduler.schedule(self, review_result, now_s),
+        }
+        self.last_review_s = Some(now_s);
+    }
+
+    /// Study days the card has gone past its due date, negative when it is not due yet
+    pub fn days_late(&self, now_s: i64, rollover_hour: i64) -> i64 {
+        match self.last_review_s {
+            Some(last_review_s) => {
+                days_between(last_review_s, now_s, rollover_hour) - self.interval_days
             }
-            // Reviewing
-            CardStatus::Review => match review_result {
-                ReviewResult::Again => {
-                    self.status = CardStatus::Learn;
-                    self.learning_step = 0;
-                    self.ease = (self.ease - 200).max(MIN_EASE);
-                    self.interval_days = 1;
-                    self.next_review_s = now_s + LEARNING_INTERVALS[0]
-                }
-                ReviewResult::Hard => {
-                    self.ease = (self.ease - 150).max(MIN_EASE);
-                    self.interval_days = ((self.interval_days as f64 * 1.2) as i64).max(1);
-                    self.next_review_s = now_s + self.interval_days * DAY;
-                }
-                ReviewResult::Good => {
-                    let mult = self.ease as f64 / 1000.0;
-                    self.interval_days = ((self.interval_days as f64 * mult) as i64).max(1);
-                    self.next_review_s = now_s + self.interval_days * DAY;
-                }
-                ReviewResult::Easy => {
-                    self.ease = (self.ease + 150).min(MAX_EASE);
-                    let mult = self.ease as f64 / 1000.0 * EASY_MULT;
-                    self.interval_days = (self.interval_days as f64 * mult) as i64;
-                    self.next_review_s = now_s + self.interval_days * DAY;
-                }
-            },
-            CardStatus::OneTimeLearned => {}
+            // Reviewed before last review times were kept
+            None => days_between(self.next_review_s, now_s, rollover_hour),
+        }
+    }
+
+    /// Flags the card as a leech once it has lapsed `threshold` times. Returns true when the card
+    /// has just become a leech. A threshold of 0 disables leech detection.
+    pub fn detect_leech(&mut self, threshold: i64) -> bool {
+        if self.leech || threshold <= 0 || self.lapses < threshold {
+            return false;
+        }
+        self.leech = true;
+        true
+    }
+
+    /// Returns the card to New, as if it had never been studied. Suspended and buried cards stay
+    /// so, and come back as New.
+    pub fn forget(&mut self, starting_ease: i64, now_s: i64) {
+        let status = self.status;
+        *self = CardState::new(self.card_id, now_s, starting_ease);
+        if matches!(status, CardStatus::Suspended | CardStatus::Buried) {
+            self.status = status;
         }
     }
+
+    /// Moves the next review to `due_s`. Cards still being (re)learned graduate, with the study days
+    /// until `due_s` as their interval.
+    pub fn set_due(&mut self, due_s: i64, now_s: i64, rollover_hour: i64) {
+        if matches!(
+            self.status,
+            CardStatus::New | CardStatus::Learn | CardStatus::Relearn
+        ) {
+            self.status = CardStatus::Review;
+            self.learning_step = 0;
+            self.interval_days = days_between(now_s, due_s, rollover_hour).max(1);
+        }
+        self.next_review_s = due_s;
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::identity_op, clippy::erasing_op)]
 mod test {
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    use super::{CardStatus, CardState, DAY, MIN, ReviewResult};
+    use crate::{
+        domain::{
+            scheduling::{DEFAULT_DAY_ROLLOVER_HOUR, Scheduling},
+            sm2_scheduler::Sm2Scheduler,
+            test_utils::{NOW, card_state},
+        },
+        utils::time_utils::{DAY, MIN, day_start_after_s},
+    };
+
+    use super::{CardState, CardStatus, ReviewResult};
+
+    /// Start of the study day `days` after today, when review cards become due
+    fn in_days(days: i64) -> i64 {
+        day_start_after_s(NOW, days, DEFAULT_DAY_ROLLOVER_HOUR)
+    }
 
     fn state_new() -> CardState {
-        CardState {
-            card_id: 0,
-            next_review_s: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i64,
-            interval_days: 1,
-            ease: 2500,
-            reps: 0,
-            lapses: 0,
-            status: CardStatus::New,
-            learning_step: 0,
-        }
+        card_state(CardStatus::New, NOW)
     }
 
     fn state_review() -> CardState {
-        CardState {
-            card_id: 0,
-            next_review_s: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i64,
-            interval_days: 1,
-            ease: 2500,
-            reps: 0,
-            lapses: 0,
-            status: CardStatus::Review,
-            learning_step: 0,
-        }
+        card_state(CardStatus::Review, NOW)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn run_test_state(
         mut card_state: CardState,
         apply: Vec<ReviewResult>,
         status: CardStatus,
         learning_step: i64,
         interval_days: i64,
-        interval: i64,
+        due: i64,
         ease: i64,
-        one_time: bool
+        one_time: bool,
     ) {
-        let now_s = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
         for review in apply {
-            card_state.apply_review(review, one_time);
+            card_state.apply_review(&Sm2Scheduler::default(), review, one_time, NOW);
         }
 
         assert_eq!(card_state.status, status);
         assert_eq!(card_state.learning_step, learning_step);
         assert_eq!(card_state.interval_days, interval_days);
-        assert!((card_state.next_review_s - now_s - interval).abs() < 2);
+        assert_eq!(card_state.next_review_s, due);
         assert_eq!(card_state.ease, ease);
     }
 
@@ -214,7 +273,7 @@ mod test {
             status: $status:ident,
             learning_step: $ls:expr,
             interval_days: $ivl:expr,
-            added_time_s: $add:expr,
+            due: $due:expr,
             ease: $ease:expr $(,)?
         ) => {
             #[test]
@@ -225,7 +284,7 @@ mod test {
                     CardStatus::$status,
                     $ls,
                     $ivl,
-                    $add,
+                    $due,
                     $ease,
                     false
                 )
@@ -238,7 +297,7 @@ mod test {
             status: $status:ident,
             learning_step: $ls:expr,
             interval_days: $ivl:expr,
-            added_time_s: $add:expr,
+            due: $due:expr,
             ease: $ease:expr $(,)?,
             one_time
         ) => {
@@ -250,7 +309,7 @@ mod test {
                     CardStatus::$status,
                     $ls,
                     $ivl,
-                    $add,
+                    $due,
                     $ease,
                     true,
                 )
@@ -266,7 +325,7 @@ mod test {
         status: Learn,
         learning_step: 0,
         interval_days: 1,
-        added_time_s: 1 * MIN,
+        due: NOW + 1 * MIN,
         ease: 2500,
         one_time
     );
@@ -278,7 +337,7 @@ mod test {
         status: OneTimeLearned,
         learning_step: 0,
         interval_days: 1,
-        added_time_s: 0 * MIN,
+        due: NOW + 0 * MIN,
         ease: 2500,
         one_time
     );
@@ -292,7 +351,7 @@ mod test {
         status: Learn,
         learning_step: 0,
         interval_days: 1,
-        added_time_s: 1 * MIN,
+        due: NOW + 1 * MIN,
         ease: 2500,
     );
 
@@ -304,7 +363,7 @@ mod test {
         status: Learn,
         learning_step: 0,
         interval_days: 1,
-        added_time_s: 1 * MIN,
+        due: NOW + 1 * MIN,
         ease: 2500,
     );
 
@@ -316,7 +375,7 @@ mod test {
         status: Learn,
         learning_step: 1,
         interval_days: 1,
-        added_time_s: 10 * MIN,
+        due: NOW + 10 * MIN,
         ease: 2500,
     );
 
@@ -327,8 +386,8 @@ mod test {
         [Easy],
         status: Review,
         learning_step: 0,
-        interval_days: 1,
-        added_time_s: 4 * DAY,
+        interval_days: 4,
+        due: in_days(4),
         ease: 2500,
     );
 
@@ -341,7 +400,7 @@ mod test {
         status: Learn,
         learning_step: 0,
         interval_days: 1,
-        added_time_s: 1 * MIN,
+        due: NOW + 1 * MIN,
         ease: 2500,
     );
 
@@ -353,7 +412,7 @@ mod test {
         status: Learn,
         learning_step: 2,
         interval_days: 1,
-        added_time_s: 1 * DAY,
+        due: NOW + 1440 * MIN,
         ease: 2500,
     );
 
@@ -364,8 +423,8 @@ mod test {
         [Easy, Easy],
         status: Review,
         learning_step: 0,
-        interval_days: 3,
-        added_time_s: 3 * DAY,
+        interval_days: 13,
+        due: in_days(13),
         ease: 2650,
     );
 
@@ -375,10 +434,10 @@ mod test {
         test_state_review_again,
         state_review(),
         [Again],
-        status: Learn,
+        status: Relearn,
         learning_step: 0,
         interval_days: 1,
-        added_time_s: 1 * MIN,
+        due: NOW + 10 * MIN,
         ease: 2300,
     );
 
@@ -390,7 +449,7 @@ mod test {
         status: Review,
         learning_step: 0,
         interval_days: 1,
-        added_time_s: 1 * DAY,
+        due: in_days(1),
         ease: 2350,
     );
 
@@ -402,7 +461,7 @@ mod test {
         status: Review,
         learning_step: 0,
         interval_days: 2,
-        added_time_s: 2 * DAY,
+        due: in_days(2),
         ease: 2500,
     );
 
@@ -414,7 +473,7 @@ mod test {
         status: Review,
         learning_step: 0,
         interval_days: 3,
-        added_time_s: 3 * DAY,
+        due: in_days(3),
         ease: 2650,
     );
 
@@ -427,7 +486,7 @@ mod test {
         status: Review,
         learning_step: 0,
         interval_days: 5,
-        added_time_s: 5 * DAY,
+        due: in_days(5),
         ease: 2500,
     );
 
@@ -439,7 +498,7 @@ mod test {
         status: Review,
         learning_step: 0,
         interval_days: 6,
-        added_time_s: 6 * DAY,
+        due: in_days(6),
         ease: 2650,
     );
 
@@ -451,7 +510,7 @@ mod test {
         status: Review,
         learning_step: 0,
         interval_days: 2,
-        added_time_s: 2 * DAY,
+        due: in_days(2),
         ease: 2350,
     );
 
@@ -463,7 +522,7 @@ mod test {
         status: Review,
         learning_step: 0,
         interval_days: 7,
-        added_time_s: 7 * DAY,
+        due: in_days(7),
         ease: 2650,
     );
 
@@ -472,10 +531,10 @@ mod test {
         test_state_review_again_good,
         state_review(),
         [Again, Good],
-        status: Learn,
-        learning_step: 1,
+        status: Review,
+        learning_step: 0,
         interval_days: 1,
-        added_time_s: 10 * MIN,
+        due: in_days(1),
         ease: 2300,
     );
 
@@ -487,7 +546,7 @@ mod test {
         status: Review,
         learning_step: 0,
         interval_days: 1,
-        added_time_s: 4 * DAY,
+        due: in_days(1),
         ease: 2300,
     );
 
@@ -496,10 +555,10 @@ mod test {
         test_state_review_again_again,
         state_review(),
         [Again, Again],
-        status: Learn,
+        status: Relearn,
         learning_step: 0,
         interval_days: 1,
-        added_time_s: 1 * MIN,
+        due: NOW + 10 * MIN,
         ease: 2300,
     );
 
@@ -511,7 +570,179 @@ mod test {
         status: Review,
         learning_step: 0,
         interval_days: 1,
-        added_time_s: 1 * DAY,
+        due: in_days(1),
         ease: 2200,
     );
+
+    #[test]
+    fn test_state_custom_scheduling() {
+        let scheduler = Sm2Scheduler {
+            scheduling: Scheduling {
+                learning_steps: vec![5],
+                graduating_days: 3,
+                ..Default::default()
+            },
+        };
+        let mut card_state = state_new();
+        card_state.apply_review(&scheduler, ReviewResult::Again, false, NOW);
+        assert_eq!(card_state.status, CardStatus::Learn);
+        assert_eq!(card_state.next_review_s, NOW + 5 * MIN);
+
+        card_state.apply_review(&scheduler, ReviewResult::Good, false, NOW);
+        assert_eq!(card_state.status, CardStatus::Review);
+        assert_eq!(card_state.interval_days, 3);
+        assert_eq!(card_state.next_review_s, in_days(3));
+    }
+
+    #[test]
+    fn test_lapse_keeps_part_of_the_interval() {
+        let scheduler = Sm2Scheduler {
+            scheduling: Scheduling {
+                relearning_steps: vec![10, 60],
+                lapse_new_interval: 0.2,
+                lapse_min_days: 3,
+                ..Default::default()
+            },
+        };
+        let mut card_state = state_review();
+        card_state.interval_days = 200;
+        card_state.apply_review(&scheduler, ReviewResult::Again, false, NOW);
+        assert_eq!(card_state.status, CardStatus::Relearn);
+        assert_eq!(card_state.interval_days, 40);
+        assert_eq!(card_state.next_review_s, NOW + 10 * MIN);
+
+        card_state.apply_review(&scheduler, ReviewResult::Good, false, NOW);
+        assert_eq!(card_state.status, CardStatus::Relearn);
+        assert_eq!(card_state.next_review_s, NOW + 60 * MIN);
+
+        card_state.apply_review(&scheduler, ReviewResult::Good, false, NOW);
+        assert_eq!(card_state.status, CardStatus::Review);
+        assert_eq!(card_state.next_review_s, in_days(40));
+
+        // Short intervals are raised to the minimum
+        card_state.interval_days = 5;
+        card_state.apply_review(&scheduler, ReviewResult::Again, false, NOW);
+        assert_eq!(card_state.interval_days, 3);
+    }
+
+    #[test]
+    fn test_overdue_credit_is_capped() {
+        let scheduler = Sm2Scheduler {
+            scheduling: Scheduling {
+                max_interval_days: 100,
+                ..Default::default()
+            },
+        };
+        let mut card_state = state_review();
+        card_state.interval_days = 10;
+        card_state.last_review_s = Some(NOW - 30 * DAY);
+
+        // 20 days late: half of them count towards Good
+        let mut good = card_state.clone();
+        good.apply_review(&scheduler, ReviewResult::Good, false, NOW);
+        assert_eq!(good.interval_days, 50);
+        assert_eq!(good.last_review_s, Some(NOW));
+
+        let mut hard = card_state.clone();
+        hard.apply_review(&scheduler, ReviewResult::Hard, false, NOW);
+        assert_eq!(hard.interval_days, 18);
+
+        let mut easy = card_state.clone();
+        easy.apply_review(&scheduler, ReviewResult::Easy, false, NOW);
+        assert_eq!(easy.interval_days, 100);
+    }
+
+    #[test]
+    fn test_review_ahead_grows_elapsed_days() {
+        let scheduler = Sm2Scheduler::default();
+        let mut card_state = state_review();
+        card_state.interval_days = 10;
+        card_state.last_review_s = Some(NOW - 8 * DAY);
+
+        // 2 days early: only the 8 elapsed days are grown
+        let mut good = card_state.clone();
+        good.apply_early_review(&scheduler, ReviewResult::Good, false, NOW);
+        assert_eq!(good.interval_days, 20);
+
+        let mut hard = card_state.clone();
+        hard.apply_early_review(&scheduler, ReviewResult::Hard, false, NOW);
+        assert_eq!(hard.interval_days, 9);
+
+        // Not served ahead, like a card moved with `card set-due`: the whole interval is grown
+        let mut good = card_state.clone();
+        good.apply_review(&scheduler, ReviewResult::Good, false, NOW);
+        assert_eq!(good.interval_days, 25);
+
+        // Reviewed the day after, the interval is kept
+        card_state.last_review_s = Some(NOW - DAY);
+        let mut good = card_state.clone();
+        good.apply_early_review(&scheduler, ReviewResult::Good, false, NOW);
+        assert_eq!(good.interval_days, 10);
+
+        let mut hard = card_state.clone();
+        hard.apply_early_review(&scheduler, ReviewResult::Hard, false, NOW);
+        assert_eq!(hard.interval_days, 6);
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        for status in [
+            CardStatus::OneTimeLearned,
+            CardStatus::Buried,
+            CardStatus::Suspended,
+            CardStatus::New,
+            CardStatus::Learn,
+            CardStatus::Review,
+            CardStatus::Relearn,
+        ] {
+            assert_eq!(CardStatus::from(status as i64), status);
+        }
+    }
+
+    #[test]
+    fn test_detect_leech() {
+        let scheduler = Sm2Scheduler::default();
+        let mut card_state = state_review();
+        for lapses in 1..=3 {
+            card_state.apply_review(&scheduler, ReviewResult::Again, false, NOW);
+            // Failing the relearning step is not another lapse
+            card_state.apply_review(&scheduler, ReviewResult::Again, false, NOW);
+            assert_eq!(card_state.lapses, lapses);
+            assert_eq!(card_state.detect_leech(3), lapses == 3);
+
+            card_state.apply_review(&scheduler, ReviewResult::Good, false, NOW);
+            assert_eq!(card_state.status, CardStatus::Review);
+        }
+        assert!(card_state.leech);
+
+        // Only reported once
+        card_state.apply_review(&scheduler, ReviewResult::Again, false, NOW);
+        assert!(!card_state.detect_leech(3));
+        assert!(!state_review().detect_leech(0));
+
+        // Nor is failing a new card
+        let mut card_state = state_new();
+        card_state.apply_review(&scheduler, ReviewResult::Again, false, NOW);
+        assert_eq!(card_state.lapses, 0);
+    }
+
+    #[test]
+    fn test_forget_and_set_due() {
+        let mut card_state = state_review();
+        card_state.apply_review(&Sm2Scheduler::default(), ReviewResult::Again, false, NOW);
+        card_state.forget(2500, NOW);
+        assert_eq!(card_state.status, CardStatus::New);
+        assert_eq!((card_state.reps, card_state.lapses), (0, 0));
+        assert_eq!(card_state.ease, 2500);
+
+        card_state.set_due(in_days(3), NOW, DEFAULT_DAY_ROLLOVER_HOUR);
+        assert_eq!(card_state.status, CardStatus::Review);
+        assert_eq!(card_state.interval_days, 3);
+        assert_eq!(card_state.next_review_s, in_days(3));
+
+        card_state.status = CardStatus::Suspended;
+        card_state.forget(2500, NOW);
+        assert_eq!(card_state.status, CardStatus::Suspended);
+        assert_eq!(card_state.reps, 0);
+    }
 }
@@ -0,0 +1,59 @@
+use super::{
+    card::Card,
+    card_state::{CardState, CardStatus},
+};
+
+/// Which cards of a deck to cram. Unset fields match every card.
+#[derive(Debug, Clone, Default)]
+pub struct CramFilter {
+    pub tag: Option<String>,
+    pub status: Option<CardStatus>,
+}
+
+impl CramFilter {
+    pub fn matches(&self, card: &Card, card_state: &CardState) -> bool {
+        self.tag.as_ref().is_none_or(|tag| card.tags.contains(tag))
+            && self.status.is_none_or(|status| card_state.status == status)
+    }
+}
+
+/// Outcome of a card in a cram session
+#[derive(Debug, Clone)]
+pub struct CramCard {
+    /// Position of the card in its deck
+    pub ord: i64,
+    pub expected_input: String,
+    /// Times the answer had to be revealed
+    pub failures: i64,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::domain::{
+        card::Card,
+        card_state::{CardState, CardStatus},
+    };
+
+    use super::CramFilter;
+
+    fn card(tags: &[&str]) -> Card {
+        Card {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cram_filter() {
+        let mut card_state = CardState::new(0, 0, 2500);
+        let filter = CramFilter {
+            tag: Some("exam".to_string()),
+            status: Some(CardStatus::Learn),
+        };
+        assert!(!filter.matches(&card(&["exam"]), &card_state));
+        card_state.status = CardStatus::Learn;
+        assert!(filter.matches(&card(&["exam"]), &card_state));
+        assert!(!filter.matches(&card(&["other"]), &card_state));
+        assert!(CramFilter::default().matches(&card(&[]), &card_state));
+    }
+}
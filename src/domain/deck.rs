@@ -1,10 +1,14 @@
 use serde::{Deserialize, Serialize};
 
-use super::card::Card;
+use super::{card::Card, scheduler::SchedulerKind, scheduling::Scheduling};
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Deck {
     pub name: String,
     pub description: String,
+    #[serde(default)]
+    pub scheduler: SchedulerKind,
+    #[serde(default)]
+    pub scheduling: Scheduling,
     pub cards: Vec<Card>,
 }
@@ -0,0 +1,204 @@
+use super::card::Card;
+
+/// How the cards of a deck file map onto the cards already stored for the deck
+#[derive(Debug, PartialEq, Eq)]
+pub struct CardMatches {
+    /// Id of the stored card each card of the file updates, None for new cards
+    pub matches: Vec<Option<i64>>,
+    /// Ids of the stored cards no longer in the file
+    pub removed: Vec<i64>,
+}
+
+/// What an update import does to the cards of a deck
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct UpdateSummary {
+    pub added: usize,
+    pub changed: usize,
+    pub removed: usize,
+}
+
+impl CardMatches {
+    pub fn summary(&self, stored: &[Card], cards: &[Card]) -> UpdateSummary {
+        let changed = cards
+            .iter()
+            .zip(&self.matches)
+            .filter_map(|(card, matched)| Some((card, (*matched)?)))
+            .filter(|(card, id)| {
+                stored
+                    .iter()
+                    .find(|s| s.id == *id)
+                    .is_some_and(|s| !s.same_content(card))
+            })
+            .count();
+        UpdateSummary {
+            added: self.matches.iter().filter(|m| m.is_none()).count(),
+            changed,
+            removed: self.removed.len(),
+        }
+    }
+}
+
+/// Matches cards with an id by that id. The others are matched by their expected input first, so
+/// reordered cards keep their progress, then by position when they are mostly unchanged, so a
+/// card edited in place, typo fixes included, does too. Stored cards with an id are only matched
+/// by it.
+pub fn match_cards(stored: &[Card], cards: &[Card]) -> CardMatches {
+    let mut used = vec![false; stored.len()];
+    let mut matches = vec![None; cards.len()];
+
+    // Matches every card left with the first stored card left that `same(card, stored)` holds
+    // for, both given by index
+    let mut match_by = |same: &dyn Fn(usize, usize) -> bool| {
+        for (i, matched) in matches.iter_mut().enumerate() {
+            if matched.is_some() {
+                continue;
+            }
+            if let Some(j) = (0..stored.len()).find(|&j| !used[j] && same(i, j)) {
+                used[j] = true;
+                *matched = Some(stored[j].id);
+            }
+        }
+    };
+    match_by(&|i, j| stored[j].key.is_some() && stored[j].key == cards[i].key);
+    match_by(&|i, j| {
+        stored[j].key.is_none() && stored[j].expected_input == cards[i].expected_input
+    });
+    match_by(&|i, j| stored[j].key.is_none() && i == j && edited_in_place(&stored[j], &cards[i]));
+
+    CardMatches {
+        matches,
+        removed: stored
+            .iter()
+            .zip(used)
+            .filter(|(_, used)| !used)
+            .map(|(card, _)| card.id)
+            .collect(),
+    }
+}
+
+/// Whether `card` looks like `stored` with small edits: same image and command, and an expected
+/// input and output close to the stored ones
+fn edited_in_place(stored: &Card, card: &Card) -> bool {
+    stored.docker_image == card.docker_image
+        && stored.command == card.command
+        && similar(&stored.expected_input, &card.expected_input)
+        && similar(&stored.expected_output, &card.expected_output)
+}
+
+/// Whether at most a third of the characters of the longest text have to change to turn one text
+/// into the other
+fn similar(a: &str, b: &str) -> bool {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    edit_distance(&a, &b) * 3 <= a.len().max(b.len())
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::domain::card::Card;
+
+    use super::{CardMatches, UpdateSummary, match_cards, similar};
+
+    fn card(id: i64, expected_input: &str) -> Card {
+        keyed(id, None, expected_input)
+    }
+
+    fn keyed(id: i64, key: Option<&str>, expected_input: &str) -> Card {
+        Card {
+            id,
+            key: key.map(str::to_string),
+            expected_input: expected_input.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_match_cards() {
+        let stored = vec![
+            card(1, "ls"),
+            card(2, "pwd"),
+            card(3, "ehco hi"),
+            card(4, "date"),
+        ];
+        let cards = vec![
+            card(0, "pwd"),
+            card(0, "ls"),
+            card(0, "echo hi"),
+            card(0, "whoami"),
+            card(0, "uptime"),
+        ];
+
+        // Reordered cards match by input and the fixed typo by position. A different card in the
+        // place of another is a new one.
+        assert_eq!(
+            match_cards(&stored, &cards),
+            CardMatches {
+                matches: vec![Some(2), Some(1), Some(3), None, None],
+                removed: vec![4],
+            }
+        );
+        assert_eq!(
+            match_cards(&stored, &cards).summary(&stored, &cards),
+            UpdateSummary {
+                added: 2,
+                changed: 1,
+                removed: 1,
+            }
+        );
+
+        assert_eq!(
+            match_cards(&stored, &cards[..2]),
+            CardMatches {
+                matches: vec![Some(2), Some(1)],
+                removed: vec![3, 4],
+            }
+        );
+    }
+
+    #[test]
+    fn test_match_cards_by_key() {
+        let stored = vec![
+            keyed(1, Some("list"), "ls"),
+            card(2, "pwd"),
+            card(3, "date"),
+        ];
+        let cards = vec![
+            card(0, "pwd"),
+            keyed(0, Some("list"), "ls -l"),
+            keyed(0, Some("time"), "date"),
+            keyed(0, Some("new"), "uptime"),
+        ];
+
+        // Cards newly given an id still match the stored card without one
+        assert_eq!(
+            match_cards(&stored, &cards),
+            CardMatches {
+                matches: vec![Some(2), Some(1), Some(3), None],
+                removed: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_similar() {
+        assert!(similar("ehco hi", "echo hi"));
+        assert!(similar("jq .name", "jq '.name'"));
+        assert!(similar("", ""));
+        assert!(!similar("date", "whoami"));
+        assert!(!similar("ls", ""));
+    }
+}
@@ -0,0 +1,77 @@
+use std::str::FromStr;
+
+use crate::utils::time_utils::{date_start_s, day_start_after_s};
+
+/// Most days a count of days given on the command line can span, about a hundred years, so that
+/// adding them to a calendar date can't overflow
+pub const MAX_DAYS: i64 = 36500;
+
+/// A day given on the command line, either as a local date, `2026-12-01`, or as a number of days
+/// from today, `+3d`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DueDate {
+    Date { year: i64, month: i64, day: i64 },
+    InDays(i64),
+}
+
+impl DueDate {
+    /// Start of the study day this refers to
+    pub fn day_start_s(&self, now_s: i64, rollover_hour: i64) -> i64 {
+        match *self {
+            DueDate::Date { year, month, day } => date_start_s(year, month, day, rollover_hour)
+                .expect("dates are validated when parsed"),
+            DueDate::InDays(days) => day_start_after_s(now_s, days, rollover_hour),
+        }
+    }
+}
+
+impl FromStr for DueDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid date `{s}`, expected YYYY-MM-DD or +Nd");
+        if let Some(days) = s.strip_prefix('+').and_then(|s| s.strip_suffix('d')) {
+            return match days.parse() {
+                Ok(days) if (0..=MAX_DAYS).contains(&days) => Ok(DueDate::InDays(days)),
+                _ => Err(invalid()),
+            };
+        }
+
+        let parts = s
+            .split('-')
+            .map(|part| part.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        match parts[..] {
+            [year, month, day] if date_start_s(year, month, day, 0).is_some() => {
+                Ok(DueDate::Date { year, month, day })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DueDate;
+
+    #[test]
+    fn test_parse_due_date() {
+        assert_eq!("+3d".parse(), Ok(DueDate::InDays(3)));
+        assert_eq!(
+            "2026-12-01".parse(),
+            Ok(DueDate::Date {
+                year: 2026,
+                month: 12,
+                day: 1
+            })
+        );
+        assert!("3d".parse::<DueDate>().is_err());
+        assert!("+-3d".parse::<DueDate>().is_err());
+        assert_eq!("+36500d".parse(), Ok(DueDate::InDays(36500)));
+        assert!("+36501d".parse::<DueDate>().is_err());
+        assert!("2026-12".parse::<DueDate>().is_err());
+        assert!("2026-02-30".parse::<DueDate>().is_err());
+        assert!("tomorrow".parse::<DueDate>().is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use std::mem;
+
+use crate::utils::time_utils::{DAY, day_start_after_s};
+
+use super::{
+    card_state::{CardState, CardStatus, ReviewResult},
+    review_queue::DeckQueue,
+};
+
+/// Cards expected to be due on one study day
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ForecastDay {
+    /// Start of the study day
+    pub start_s: i64,
+    pub review: i64,
+    pub learn: i64,
+    pub new: i64,
+}
+
+impl ForecastDay {
+    pub fn total(&self) -> i64 {
+        self.review + self.learn + self.new
+    }
+}
+
+/// Projects the workload of a deck for the next `days` study days, starting with the one that
+/// began at `today_start_s`. Overdue cards count towards today. Review cards are assumed to be
+/// answered Good on the day they are shown, and come back when the deck scheduler says. Reviews
+/// past the deck daily limit wait for the next day. New cards are introduced at the deck daily
+/// limit.
+pub fn forecast(deck: &DeckQueue, today_start_s: i64, days: usize) -> Vec<ForecastDay> {
+    let mut forecast: Vec<_> = (0..days)
+        .map(|day| ForecastDay {
+            start_s: day_start_after_s(
+                today_start_s,
+                day as i64,
+                deck.scheduling.day_rollover_hour,
+            ),
+            ..Default::default()
+        })
+        .collect();
+    let day_of = |timestamp_s: i64| ((timestamp_s - today_start_s).max(0) / DAY) as usize;
+    let scheduler = deck.scheduling.scheduler(deck.scheduler);
+    // Review cards due each day, backlog first
+    let mut reviews_due: Vec<Vec<CardState>> = vec![vec![]; days];
+
+    for (_, card_state) in &deck.cards {
+        match card_state.status {
+            CardStatus::Learn | CardStatus::Relearn => {
+                if let Some(day) = forecast.get_mut(day_of(card_state.next_review_s)) {
+                    day.learn += 1;
+                }
+            }
+            CardStatus::Review => {
+                if let Some(due) = reviews_due.get_mut(day_of(card_state.next_review_s)) {
+                    due.push(card_state.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for day in 0..days {
+        let limit = match day {
+            0 => deck.reviews_left,
+            _ => deck.scheduling.reviews_per_day,
+        };
+        let mut due = mem::take(&mut reviews_due[day]);
+        let mut backlog = due.split_off((limit.max(0) as usize).min(due.len()));
+        if let Some(next_due) = reviews_due.get_mut(day + 1) {
+            backlog.append(next_due);
+            *next_due = backlog;
+        }
+
+        let review_s = today_start_s + day as i64 * DAY;
+        for mut card_state in due {
+            forecast[day].review += 1;
+            card_state.apply_review(scheduler.as_ref(), ReviewResult::Good, false, review_s);
+            let next_day = day_of(card_state.next_review_s).max(day + 1);
+            if let Some(due) = reviews_due.get_mut(next_day) {
+                due.push(card_state);
+            }
+        }
+    }
+
+    let mut new_left = deck
+        .cards
+        .iter()
+        .filter(|(_, cs)| cs.status == CardStatus::New)
+        .count() as i64;
+    for (i, day) in forecast.iter_mut().enumerate() {
+        let limit = if i == 0 {
+            deck.new_left
+        } else {
+            deck.scheduling.new_per_day
+        };
+        day.new = new_left.min(limit.max(0));
+        new_left -= day.new;
+    }
+
+    forecast
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        domain::{
+            card::Card,
+            card_state::{CardState, CardStatus, ReviewResult},
+            review_queue::DeckQueue,
+            scheduler::SchedulerKind,
+            scheduling::{DEFAULT_DAY_ROLLOVER_HOUR, Scheduling},
+            test_utils::{NOW, card_state},
+        },
+        utils::time_utils::{DAY, MIN, day_start_after_s, day_start_s},
+    };
+
+    use super::forecast;
+
+    fn today() -> i64 {
+        day_start_s(NOW, DEFAULT_DAY_ROLLOVER_HOUR)
+    }
+
+    fn card(status: CardStatus, next_review_s: i64, interval_days: i64) -> (Card, CardState) {
+        (
+            Card::default(),
+            CardState {
+                interval_days,
+                ease: 2000,
+                ..card_state(status, next_review_s)
+            },
+        )
+    }
+
+    fn deck(scheduler: SchedulerKind, cards: Vec<(Card, CardState)>) -> DeckQueue {
+        DeckQueue {
+            scheduler,
+            scheduling: Scheduling {
+                new_per_day: 2,
+                reviews_per_day: 2,
+                ..Default::default()
+            },
+            cards,
+            new_left: 1,
+            reviews_left: 2,
+            plan: None,
+        }
+    }
+
+    #[test]
+    fn test_forecast() {
+        let deck = deck(
+            SchedulerKind::Sm2,
+            vec![
+                card(CardStatus::Review, today() - 3 * DAY, 1),
+                card(CardStatus::Learn, today() + DAY + 10 * MIN, 1),
+                card(CardStatus::New, today(), 1),
+                card(CardStatus::New, today(), 1),
+                card(CardStatus::New, today(), 1),
+            ],
+        );
+
+        let days = forecast(&deck, today(), 7);
+        let counts: Vec<_> = days
+            .iter()
+            .map(|day| (day.review, day.learn, day.new))
+            .collect();
+        // The review card is overdue, and comes back after its interval plus half the days it
+        // was overdue, times the 2.0 ease
+        assert_eq!(
+            counts,
+            vec![
+                (1, 0, 1),
+                (0, 1, 2),
+                (0, 0, 0),
+                (0, 0, 0),
+                (0, 0, 0),
+                (1, 0, 0),
+                (0, 0, 0),
+            ]
+        );
+        for (i, day) in days.iter().enumerate() {
+            let start_s = day_start_after_s(NOW, i as i64, DEFAULT_DAY_ROLLOVER_HOUR);
+            assert_eq!(day.start_s, start_s);
+        }
+    }
+
+    #[test]
+    fn test_forecast_caps_reviews() {
+        let deck = deck(
+            SchedulerKind::Sm2,
+            vec![card(CardStatus::Review, today(), 1); 5],
+        );
+
+        // Two a day, the rest wait
+        let reviews: Vec<_> = forecast(&deck, today(), 3)
+            .iter()
+            .map(|day| day.review)
+            .collect();
+        assert_eq!(reviews, vec![2, 2, 2]);
+    }
+
+    #[test]
+    fn test_forecast_fsrs() {
+        let mut review = card(CardStatus::Review, today(), 1);
+        review.1.stability = 20.0;
+        review.1.difficulty = 5.0;
+        let deck = deck(SchedulerKind::Fsrs, vec![review.clone()]);
+
+        let mut expected = review.1;
+        expected.apply_review(
+            deck.scheduling.scheduler(SchedulerKind::Fsrs).as_ref(),
+            ReviewResult::Good,
+            false,
+            today(),
+        );
+        let next_day = ((expected.next_review_s - today()) / DAY) as usize;
+        // Far later than the 2.0 ease would bring it back
+        assert!(next_day > 2);
+
+        let days = forecast(&deck, today(), next_day + 1);
+        assert_eq!(days[0].review, 1);
+        assert_eq!(days[next_day].review, 1);
+        assert_eq!(days.iter().map(|day| day.review).sum::<i64>(), 2);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::utils::time_utils::{DAY, MIN};
+
+use super::{
+    card_state::{CardState, CardStatus, ReviewResult},
+    scheduler::{DECAY, FACTOR, Scheduler, forgetting_curve, relearn, review_due_s},
+    scheduling::Scheduling,
+};
+
+pub const DEFAULT_WEIGHTS: [f64; 17] = [
+    0.4872, 1.4003, 3.7145, 13.8206, 5.1618, 1.2298, 0.8975, 0.031, 1.6474, 0.1367, 1.0461, 2.1072,
+    0.0793, 0.3246, 1.587, 0.2272, 2.8755,
+];
+pub const DEFAULT_DESIRED_RETENTION: f64 = 0.9;
+
+/// Steps of new cards before they graduate, the deck `learning_steps` are for SM-2 only
+const LEARNING_INTERVALS: [i64; 2] = [MIN, 10 * MIN];
+
+/// Free Spaced Repetition Scheduler: tracks stability (days until retrievability drops to 90%)
+/// and difficulty (1-10) per card, and schedules the next review when the predicted
+/// retrievability reaches `desired_retention`. The fields are the deck [`Scheduling`] parameters
+/// of the same name.
+#[derive(Debug, Clone)]
+pub struct FsrsScheduler {
+    /// The deck `fsrs_weights`
+    pub weights: [f64; 17],
+    pub desired_retention: f64,
+    pub day_rollover_hour: i64,
+    pub relearning_steps: Vec<i64>,
+    pub lapse_min_days: i64,
+    pub max_interval_days: i64,
+}
+
+impl Default for FsrsScheduler {
+    fn default() -> Self {
+        FsrsScheduler::new(&Scheduling::default())
+    }
+}
+
+impl FsrsScheduler {
+    pub fn new(scheduling: &Scheduling) -> FsrsScheduler {
+        FsrsScheduler {
+            weights: scheduling.fsrs_weights,
+            desired_retention: scheduling.desired_retention,
+            day_rollover_hour: scheduling.day_rollover_hour,
+            relearning_steps: scheduling.relearning_steps.clone(),
+            lapse_min_days: scheduling.lapse_min_days,
+            max_interval_days: scheduling.max_interval_days,
+        }
+    }
+
+    fn next_interval_days(&self, stability: f64) -> i64 {
+        let days = stability / FACTOR * (self.desired_retention.powf(1.0 / DECAY) - 1.0);
+        (days.round() as i64).clamp(1, self.max_interval_days.max(1))
+    }
+
+    fn init_stability(&self, grade: f64) -> f64 {
+        self.weights[grade as usize - 1].max(0.1)
+    }
+
+    fn init_difficulty(&self, grade: f64) -> f64 {
+        (self.weights[4] - (grade - 3.0) * self.weights[5]).clamp(1.0, 10.0)
+    }
+
+    fn next_difficulty(&self, difficulty: f64, grade: f64) -> f64 {
+        let w = &self.weights;
+        let difficulty = difficulty - w[6] * (grade - 3.0);
+        // Mean reversion towards the difficulty of a card first rated Good
+        (w[7] * self.init_difficulty(3.0) + (1.0 - w[7]) * difficulty).clamp(1.0, 10.0)
+    }
+
+    fn recall_stability(
+        &self,
+        difficulty: f64,
+        stability: f64,
+        retrievability: f64,
+        review_result: ReviewResult,
+    ) -> f64 {
+        let w = &self.weights;
+        let hard_penalty = if review_result == ReviewResult::Hard {
+            w[15]
+        } else {
+            1.0
+        };
+        let easy_bonus = if review_result == ReviewResult::Easy {
+            w[16]
+        } else {
+            1.0
+        };
+        stability
+            * (1.0
+                + w[8].exp()
+                    * (11.0 - difficulty)
+                    * stability.powf(-w[9])
+                    * ((w[10] * (1.0 - retrievability)).exp() - 1.0)
+                    * hard_penalty
+                    * easy_bonus)
+    }
+
+    fn forget_stability(&self, difficulty: f64, stability: f64, retrievability: f64) -> f64 {
+        let w = &self.weights;
+        let new_stability = w[11]
+            * difficulty.powf(-w[12])
+            * ((stability + 1.0).powf(w[13]) - 1.0)
+            * (w[14] * (1.0 - retrievability)).exp();
+        new_stability.min(stability)
+    }
+}
+
+impl Scheduler for FsrsScheduler {
+    fn schedule(&self, card_state: &mut CardState, review_result: ReviewResult, now_s: i64) {
+        let grade = review_result as i64 as f64;
+
+        match card_state.status {
+            CardStatus::New | CardStatus::Learn => {
+                if card_state.status == CardStatus::New {
+                    card_state.stability = self.init_stability(grade);
+                    card_state.difficulty = self.init_difficulty(grade);
+                }
+                card_state.status = CardStatus::Learn;
+                match review_result {
+                    ReviewResult::Again => {
+                        card_state.learning_step = 0;
+                        card_state.next_review_s = now_s + LEARNING_INTERVALS[0];
+                    }
+                    ReviewResult::Hard => {
+                        card_state.learning_step = 1;
+                        card_state.next_review_s = now_s + LEARNING_INTERVALS[1];
+                    }
+                    ReviewResult::Good | ReviewResult::Easy => {
+                        card_state.status = CardStatus::Review;
+                        card_state.learning_step = 0;
+                        card_state.interval_days = self.next_interval_days(card_state.stability);
+                        card_state.next_review_s =
+                            review_due_s(now_s, card_state.interval_days, self.day_rollover_hour);
+                    }
+                }
+            }
+            CardStatus::Review => {
+                // Cards reviewed under SM-2 have no memory state yet, seed it from the interval
+                if card_state.stability <= 0.0 {
+                    card_state.stability = card_state.interval_days.max(1) as f64;
+                    card_state.difficulty = self.init_difficulty(3.0);
+                }
+
+                let last_review_s = card_state
+                    .last_review_s
+                    .unwrap_or(card_state.next_review_s - card_state.interval_days * DAY);
+                let elapsed_days = ((now_s - last_review_s).max(0) as f64) / DAY as f64;
+                let retrievability = forgetting_curve(elapsed_days, card_state.stability);
+
+                card_state.difficulty = self.next_difficulty(card_state.difficulty, grade);
+                if review_result == ReviewResult::Again {
+                    card_state.stability = self.forget_stability(
+                        card_state.difficulty,
+                        card_state.stability,
+                        retrievability,
+                    );
+                    card_state.interval_days = self
+                        .next_interval_days(card_state.stability)
+                        .max(self.lapse_min_days);
+                    card_state.status = CardStatus::Relearn;
+                    card_state.learning_step = 0;
+                    if relearn(card_state, review_result, &self.relearning_steps, now_s) {
+                        card_state.next_review_s =
+                            review_due_s(now_s, card_state.interval_days, self.day_rollover_hour);
+                    }
+                } else {
+                    card_state.stability = self.recall_stability(
+                        card_state.difficulty,
+                        card_state.stability,
+                        retrievability,
+                        review_result,
+                    );
+                    card_state.interval_days = self.next_interval_days(card_state.stability);
+                    card_state.next_review_s =
+                        review_due_s(now_s, card_state.interval_days, self.day_rollover_hour);
+                }
+            }
+            // Relearning, stability was already updated when the card lapsed
+            CardStatus::Relearn => {
+                if relearn(card_state, review_result, &self.relearning_steps, now_s) {
+                    card_state.next_review_s =
+                        review_due_s(now_s, card_state.interval_days, self.day_rollover_hour);
+                }
+            }
+            CardStatus::OneTimeLearned | CardStatus::Suspended | CardStatus::Buried => {}
+        }
+    }
+
+    fn recall_probability(&self, card_state: &CardState, elapsed_days: f64) -> f64 {
+        let stability = if card_state.stability > 0.0 {
+            card_state.stability
+        } else {
+            card_state.interval_days.max(1) as f64
+        };
+        forgetting_curve(elapsed_days, stability)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        domain::{
+            scheduling::DEFAULT_DAY_ROLLOVER_HOUR,
+            test_utils::{NOW, card_state},
+        },
+        utils::time_utils::{MIN, day_start_after_s},
+    };
+
+    use super::{CardState, CardStatus, FsrsScheduler, ReviewResult, Scheduler};
+
+    fn state(status: CardStatus) -> CardState {
+        card_state(status, NOW)
+    }
+
+    #[test]
+    fn test_fsrs_new_good_graduates_after_initial_stability() {
+        let mut card_state = state(CardStatus::New);
+        FsrsScheduler::default().schedule(&mut card_state, ReviewResult::Good, NOW);
+
+        assert_eq!(card_state.status, CardStatus::Review);
+        // At 90% desired retention the interval equals the stability
+        assert_eq!(card_state.interval_days, 4);
+        assert_eq!(
+            card_state.next_review_s,
+            day_start_after_s(NOW, 4, DEFAULT_DAY_ROLLOVER_HOUR)
+        );
+    }
+
+    #[test]
+    fn test_fsrs_new_again_stays_in_learning() {
+        let mut card_state = state(CardStatus::New);
+        FsrsScheduler::default().schedule(&mut card_state, ReviewResult::Again, NOW);
+
+        assert_eq!(card_state.status, CardStatus::Learn);
+        assert_eq!(card_state.next_review_s, NOW + MIN);
+        assert!(card_state.difficulty > FsrsScheduler::default().init_difficulty(3.0));
+    }
+
+    #[test]
+    fn test_fsrs_review_grows_and_lapse_shrinks_stability() {
+        let scheduler = FsrsScheduler::default();
+        let mut card_state = state(CardStatus::New);
+        scheduler.schedule(&mut card_state, ReviewResult::Good, NOW);
+
+        let due = card_state.next_review_s;
+        let mut remembered = card_state.clone();
+        scheduler.schedule(&mut remembered, ReviewResult::Good, due);
+        assert!(remembered.stability > card_state.stability);
+        assert!(remembered.interval_days > card_state.interval_days);
+
+        let mut forgotten = card_state.clone();
+        scheduler.schedule(&mut forgotten, ReviewResult::Again, due);
+        assert!(forgotten.stability < card_state.stability);
+        assert_eq!(forgotten.status, CardStatus::Relearn);
+        assert_eq!(forgotten.next_review_s, due + 10 * MIN);
+    }
+}
@@ -0,0 +1,11 @@
+use super::card_state::CardStatus;
+
+/// A card flagged as a leech, as listed by `termcards leeches`
+#[derive(Debug, Clone)]
+pub struct Leech {
+    pub deck_name: String,
+    pub ord: i64,
+    pub lapses: i64,
+    pub status: CardStatus,
+    pub expected_input: String,
+}
@@ -0,0 +1,73 @@
+use rand::{Rng, seq::IndexedRandom};
+
+/// Intervals, in days, a review interval may be moved to, so cards answered the same way on the
+/// same day drift apart. Short intervals are left alone.
+pub fn fuzz_range(interval_days: i64, max_interval_days: i64) -> (i64, i64) {
+    let fuzz = match interval_days {
+        ..3 => return (interval_days, interval_days),
+        3..7 => interval_days as f64 * 0.15,
+        7..20 => interval_days as f64 * 0.1,
+        _ => interval_days as f64 * 0.05,
+    };
+    let fuzz = (fuzz.round() as i64).max(1);
+    let max = (interval_days + fuzz).min(max_interval_days);
+    ((interval_days - fuzz).min(max), max)
+}
+
+/// Picks the interval in `range` whose due day has the fewest cards, `due_counts[i]` being the
+/// cards due `range.0 + i` days from today. Ties are broken at random.
+pub fn balance(range: (i64, i64), due_counts: &[i64], rng: &mut impl Rng) -> i64 {
+    let (min, max) = range;
+    let count = |interval: i64| {
+        due_counts
+            .get((interval - min) as usize)
+            .copied()
+            .unwrap_or(0)
+    };
+    let lowest = (min..=max).map(count).min().unwrap_or(0);
+    let candidates: Vec<i64> = (min..=max).filter(|&ivl| count(ivl) == lowest).collect();
+    *candidates.choose(rng).unwrap_or(&min)
+}
+
+/// Days, counted from `first_day`, to move `count` cards to so they are spread evenly over
+/// `days` days, keeping their order
+pub fn spread(count: usize, first_day: i64, days: i64) -> Vec<i64> {
+    let days = days.max(1);
+    (0..count as i64)
+        .map(|i| first_day + i * days / count as i64)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::{balance, fuzz_range, spread};
+
+    #[test]
+    fn test_fuzz_range() {
+        assert_eq!(fuzz_range(1, 36500), (1, 1));
+        assert_eq!(fuzz_range(4, 36500), (3, 5));
+        assert_eq!(fuzz_range(10, 36500), (9, 11));
+        assert_eq!(fuzz_range(100, 36500), (95, 105));
+        assert_eq!(fuzz_range(100, 100), (95, 100));
+    }
+
+    #[test]
+    fn test_balance_prefers_the_quietest_day() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(balance((9, 11), &[5, 2, 7], &mut rng), 10);
+        for _ in 0..20 {
+            let interval = balance((9, 11), &[3, 8, 3], &mut rng);
+            assert!(interval == 9 || interval == 11);
+        }
+    }
+
+    #[test]
+    fn test_spread() {
+        assert_eq!(spread(6, 3, 3), vec![3, 3, 4, 4, 5, 5]);
+        assert_eq!(spread(2, 3, 4), vec![3, 5]);
+        assert_eq!(spread(1, 0, 0), vec![0]);
+        assert!(spread(0, 3, 3).is_empty());
+    }
+}
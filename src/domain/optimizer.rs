@@ -0,0 +1,276 @@
+use crate::utils::time_utils::DAY;
+
+use super::{
+    card_state::{CardState, CardStatus, ReviewResult},
+    scheduler::SchedulerKind,
+    scheduling::Scheduling,
+};
+
+// Pattern search: every parameter starts moving by a tenth of its range, and the steps are halved
+// whenever no move improves the loss, until they are this small compared to the range.
+const INITIAL_STEP: f64 = 0.1;
+const MIN_STEP: f64 = 0.001;
+const MAX_ROUNDS: usize = 500;
+
+const FSRS_WEIGHT_BOUNDS: [(f64, f64); 17] = [
+    (0.01, 100.0),
+    (0.01, 100.0),
+    (0.01, 100.0),
+    (0.01, 100.0),
+    (1.0, 10.0),
+    (0.001, 4.0),
+    (0.001, 4.0),
+    (0.001, 0.75),
+    (0.0, 4.5),
+    (0.0, 0.8),
+    (0.001, 3.5),
+    (0.001, 5.0),
+    (0.001, 0.25),
+    (0.001, 0.9),
+    (0.0, 4.0),
+    (0.0, 1.0),
+    (1.0, 6.0),
+];
+
+/// A rating taken from the review log
+#[derive(Debug, Clone, Copy)]
+pub struct ReviewEvent {
+    pub timestamp: i64,
+    pub result: ReviewResult,
+}
+
+/// How well a set of scheduling parameters predicts the outcome of past reviews
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Evaluation {
+    /// Mean binary cross-entropy of the predicted recall probability
+    pub log_loss: f64,
+    pub predicted_retention: f64,
+    pub actual_retention: f64,
+    pub reviews: usize,
+}
+
+pub struct Fit {
+    pub scheduling: Scheduling,
+    pub before: Evaluation,
+    pub after: Evaluation,
+}
+
+/// Replays the review history of every card with the given parameters, and compares the recall
+/// probability predicted before each review of a graduated card with what actually happened.
+/// `histories` holds the ratings of each card, oldest first.
+pub fn evaluate(
+    kind: SchedulerKind,
+    scheduling: &Scheduling,
+    histories: &[Vec<ReviewEvent>],
+) -> Evaluation {
+    let scheduler = scheduling.scheduler(kind);
+    let mut evaluation = Evaluation::default();
+
+    for history in histories {
+        let Some(first) = history.first() else {
+            continue;
+        };
+        let mut card_state = CardState::new(0, first.timestamp, scheduling.starting_ease);
+        let mut last_review_s = first.timestamp;
+
+        for event in history {
+            if card_state.status == CardStatus::Review {
+                let elapsed_days = (event.timestamp - last_review_s).max(0) as f64 / DAY as f64;
+                let p = scheduler
+                    .recall_probability(&card_state, elapsed_days)
+                    .clamp(1e-4, 1.0 - 1e-4);
+                let recalled = event.result != ReviewResult::Again;
+
+                evaluation.log_loss -= if recalled { p.ln() } else { (1.0 - p).ln() };
+                evaluation.predicted_retention += p;
+                evaluation.actual_retention += if recalled { 1.0 } else { 0.0 };
+                evaluation.reviews += 1;
+            }
+            card_state.apply_review(scheduler.as_ref(), event.result, false, event.timestamp);
+            last_review_s = event.timestamp;
+        }
+    }
+
+    if evaluation.reviews > 0 {
+        let reviews = evaluation.reviews as f64;
+        evaluation.log_loss /= reviews;
+        evaluation.predicted_retention /= reviews;
+        evaluation.actual_retention /= reviews;
+    }
+    evaluation
+}
+
+/// Fits the parameters of the deck scheduler to its review history by minimising log-loss.
+/// Returns `None` when no graduated card has been reviewed yet, as there is nothing to fit.
+pub fn optimize(
+    kind: SchedulerKind,
+    scheduling: &Scheduling,
+    histories: &[Vec<ReviewEvent>],
+) -> Option<Fit> {
+    let before = evaluate(kind, scheduling, histories);
+    if before.reviews == 0 {
+        return None;
+    }
+
+    let bounds = param_bounds(kind);
+    let mut params = get_params(kind, scheduling);
+    let mut steps: Vec<f64> = bounds
+        .iter()
+        .map(|(min, max)| (max - min) * INITIAL_STEP)
+        .collect();
+    let mut best = before.log_loss;
+    let mut candidate = scheduling.clone();
+
+    for _ in 0..MAX_ROUNDS {
+        let mut improved = false;
+        for i in 0..params.len() {
+            for direction in [1.0, -1.0] {
+                let mut moved = params.clone();
+                moved[i] = (moved[i] + direction * steps[i]).clamp(bounds[i].0, bounds[i].1);
+                if moved[i] == params[i] {
+                    continue;
+                }
+                set_params(kind, &mut candidate, &moved);
+                let loss = evaluate(kind, &candidate, histories).log_loss;
+                if loss < best {
+                    best = loss;
+                    params = moved;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+
+        if !improved {
+            for (step, (min, max)) in steps.iter_mut().zip(&bounds) {
+                *step = (*step / 2.0).max((max - min) * MIN_STEP / 2.0);
+            }
+            let converged = steps
+                .iter()
+                .zip(&bounds)
+                .all(|(step, (min, max))| *step <= (max - min) * MIN_STEP);
+            if converged {
+                break;
+            }
+        }
+    }
+
+    let mut fitted = scheduling.clone();
+    set_params(kind, &mut fitted, &params);
+    let after = evaluate(kind, &fitted, histories);
+    Some(Fit {
+        scheduling: fitted,
+        before,
+        after,
+    })
+}
+
+fn param_bounds(kind: SchedulerKind) -> Vec<(f64, f64)> {
+    match kind {
+        SchedulerKind::Sm2 => vec![
+            (0.0, 500.0), // again_ease_delta
+            (0.0, 400.0), // hard_ease_delta
+            (0.0, 400.0), // easy_ease_delta
+            (1.0, 2.0),   // hard_mult
+            (1.0, 3.0),   // easy_mult
+            (0.5, 2.0),   // interval_mult
+        ],
+        SchedulerKind::Fsrs => FSRS_WEIGHT_BOUNDS.to_vec(),
+    }
+}
+
+fn get_params(kind: SchedulerKind, s: &Scheduling) -> Vec<f64> {
+    match kind {
+        SchedulerKind::Sm2 => vec![
+            s.again_ease_delta as f64,
+            s.hard_ease_delta as f64,
+            s.easy_ease_delta as f64,
+            s.hard_mult,
+            s.easy_mult,
+            s.interval_mult,
+        ],
+        SchedulerKind::Fsrs => s.fsrs_weights.to_vec(),
+    }
+}
+
+fn set_params(kind: SchedulerKind, s: &mut Scheduling, params: &[f64]) {
+    match kind {
+        SchedulerKind::Sm2 => {
+            s.again_ease_delta = params[0].round() as i64;
+            s.hard_ease_delta = params[1].round() as i64;
+            s.easy_ease_delta = params[2].round() as i64;
+            s.hard_mult = params[3];
+            s.easy_mult = params[4];
+            s.interval_mult = params[5];
+        }
+        SchedulerKind::Fsrs => s.fsrs_weights.copy_from_slice(params),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        domain::{
+            card_state::ReviewResult, scheduler::SchedulerKind, scheduling::Scheduling,
+            test_utils::NOW,
+        },
+        utils::time_utils::{DAY, MIN},
+    };
+
+    use super::{ReviewEvent, evaluate, optimize};
+
+    /// A card learnt in one session, then reviewed every `gap_days` with the given results
+    fn history(gap_days: i64, reviews: &[ReviewResult]) -> Vec<ReviewEvent> {
+        let mut events = vec![
+            ReviewEvent {
+                timestamp: NOW,
+                result: ReviewResult::Good,
+            },
+            ReviewEvent {
+                timestamp: NOW + 10 * MIN,
+                result: ReviewResult::Good,
+            },
+            ReviewEvent {
+                timestamp: NOW + DAY,
+                result: ReviewResult::Good,
+            },
+        ];
+        for (i, result) in reviews.iter().enumerate() {
+            events.push(ReviewEvent {
+                timestamp: NOW + DAY + (i as i64 + 1) * gap_days * DAY,
+                result: *result,
+            });
+        }
+        events
+    }
+
+    #[test]
+    fn test_evaluate_only_counts_graduated_reviews() {
+        let histories = vec![history(2, &[ReviewResult::Good, ReviewResult::Again])];
+        let evaluation = evaluate(SchedulerKind::Sm2, &Scheduling::default(), &histories);
+
+        assert_eq!(evaluation.reviews, 2);
+        assert_eq!(evaluation.actual_retention, 0.5);
+    }
+
+    #[test]
+    fn test_optimize_without_reviews() {
+        let histories = vec![history(1, &[])];
+        assert!(optimize(SchedulerKind::Sm2, &Scheduling::default(), &histories).is_none());
+    }
+
+    #[test]
+    fn test_optimize_lowers_log_loss() {
+        // Cards reviewed every day and never forgotten: the fitted parameters should predict a
+        // higher retention than the defaults
+        let histories: Vec<_> = (0..10)
+            .map(|_| history(1, &[ReviewResult::Good; 5]))
+            .collect();
+
+        for kind in [SchedulerKind::Sm2, SchedulerKind::Fsrs] {
+            let fit = optimize(kind, &Scheduling::default(), &histories).unwrap();
+            assert!(fit.after.log_loss < fit.before.log_loss);
+            assert!(fit.after.predicted_retention > fit.before.predicted_retention);
+        }
+    }
+}
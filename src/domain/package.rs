@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use super::deck::Deck;
+
+/// Extension of single-file deck packages, gzipped tar archives of a deck file, the sources of
+/// its volume mounts and optionally a Dockerfile
+pub const PACKAGE_EXTENSION: &str = "tcdeck";
+pub const PACKAGE_DECK_FILE: &str = "deck.toml";
+pub const PACKAGE_DOCKERFILE: &str = "Dockerfile";
+const PACKAGE_VOLUMES_DIR: &str = "volumes";
+
+/// Points the volume mounts of a deck to directories inside a package, relative to its deck file.
+/// Returns the directories to pack, as (source, path in the package), each source once.
+pub fn relocate_mounts(deck: &mut Deck) -> Vec<(String, String)> {
+    let mut dirs: Vec<(String, String)> = vec![];
+    for (host, _) in deck
+        .cards
+        .iter_mut()
+        .flat_map(|card| &mut card.volume_mounts)
+    {
+        let packed = match dirs.iter().find(|(source, _)| source == host) {
+            Some((_, packed)) => packed.clone(),
+            None => {
+                let name = Path::new(host.as_str())
+                    .file_name()
+                    .map_or("volume".to_string(), |name| {
+                        name.to_string_lossy().to_string()
+                    });
+                let mut packed = format!("{PACKAGE_VOLUMES_DIR}/{name}");
+                if dirs.iter().any(|(_, p)| *p == packed) {
+                    packed = format!("{packed}-{}", dirs.len());
+                }
+                dirs.push((host.clone(), packed.clone()));
+                packed
+            }
+        };
+        *host = packed;
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod test {
+    use crate::domain::{card::Card, deck::Deck};
+
+    use super::relocate_mounts;
+
+    fn card(mounts: &[&str]) -> Card {
+        Card {
+            volume_mounts: mounts
+                .iter()
+                .map(|host| (host.to_string(), "/root/data".to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_relocate_mounts() {
+        let mut deck = Deck {
+            name: "jq".to_string(),
+            description: String::new(),
+            scheduler: Default::default(),
+            scheduling: Default::default(),
+            cards: vec![
+                card(&["/srv/json/login"]),
+                card(&["/srv/json/login", "/srv/other/login", "/srv/json/sales"]),
+            ],
+        };
+        let dirs = relocate_mounts(&mut deck);
+
+        assert_eq!(
+            dirs,
+            vec![
+                ("/srv/json/login".to_string(), "volumes/login".to_string()),
+                (
+                    "/srv/other/login".to_string(),
+                    "volumes/login-1".to_string()
+                ),
+                ("/srv/json/sales".to_string(), "volumes/sales".to_string()),
+            ]
+        );
+        let hosts: Vec<&str> = deck.cards[1]
+            .volume_mounts
+            .iter()
+            .map(|(host, _)| host.as_str())
+            .collect();
+        assert_eq!(
+            hosts,
+            vec!["volumes/login", "volumes/login-1", "volumes/sales"]
+        );
+    }
+}
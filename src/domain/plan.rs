@@ -0,0 +1,52 @@
+use crate::utils::time_utils::days_between;
+
+/// Study plan of a deck: every new card introduced by the end of the study day starting at
+/// `finish_by_s`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StudyPlan {
+    pub finish_by_s: i64,
+}
+
+impl StudyPlan {
+    /// Study days left in the plan, today and the last day included. At least 1, so cards left
+    /// once the date has passed are all due at once.
+    pub fn days_left(&self, now_s: i64, rollover_hour: i64) -> i64 {
+        (days_between(now_s, self.finish_by_s, rollover_hour) + 1).max(1)
+    }
+
+    /// New cards to introduce each day to finish on time, given the `new_cards` that were left at
+    /// the start of today
+    pub fn new_per_day(&self, new_cards: i64, now_s: i64, rollover_hour: i64) -> i64 {
+        let days_left = self.days_left(now_s, rollover_hour);
+        (new_cards.max(0) + days_left - 1) / days_left
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        domain::{scheduling::DEFAULT_DAY_ROLLOVER_HOUR, test_utils::NOW},
+        utils::time_utils::{DAY, day_start_after_s},
+    };
+
+    use super::StudyPlan;
+
+    #[test]
+    fn test_new_per_day() {
+        let plan = StudyPlan {
+            finish_by_s: day_start_after_s(NOW, 9, DEFAULT_DAY_ROLLOVER_HOUR),
+        };
+        assert_eq!(plan.days_left(NOW, DEFAULT_DAY_ROLLOVER_HOUR), 10);
+        assert_eq!(plan.new_per_day(100, NOW, DEFAULT_DAY_ROLLOVER_HOUR), 10);
+        assert_eq!(plan.new_per_day(101, NOW, DEFAULT_DAY_ROLLOVER_HOUR), 11);
+        assert_eq!(plan.new_per_day(0, NOW, DEFAULT_DAY_ROLLOVER_HOUR), 0);
+
+        // Recomputed from what is left as days go by
+        let later = NOW + 8 * DAY;
+        assert_eq!(plan.new_per_day(15, later, DEFAULT_DAY_ROLLOVER_HOUR), 8);
+
+        // Past the date everything left is due
+        let past = NOW + 20 * DAY;
+        assert_eq!(plan.new_per_day(15, past, DEFAULT_DAY_ROLLOVER_HOUR), 15);
+    }
+}
@@ -0,0 +1,25 @@
+use sqlx::prelude::FromRow;
+
+use super::card_state::CardStatus;
+
+/// `rating` of entries logged for manual changes to a card rather than reviews. Real ratings are
+/// 1 to 4.
+pub const RATING_FORGET: i64 = -1;
+pub const RATING_SET_DUE: i64 = -2;
+pub const RATING_POSTPONE: i64 = -3;
+
+#[derive(Debug, FromRow, Clone)]
+pub struct ReviewLog {
+    pub id: i64,
+    pub card_id: i64,
+    pub timestamp: i64,
+    pub rating: i64,
+    pub prev_ivl: i64,
+    pub new_ivl: i64,
+    pub prev_ease: i64,
+    pub new_ease: i64,
+    pub duration_ms: i64,
+    pub commands: i64,
+    pub revealed: bool,
+    pub prev_status: CardStatus,
+}
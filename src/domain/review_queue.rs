@@ -0,0 +1,217 @@
+use crate::utils::time_utils::{MIN, day_start_after_s};
+
+use super::{
+    card::Card,
+    card_state::{CardState, CardStatus},
+    plan::StudyPlan,
+    scheduler::SchedulerKind,
+    scheduling::Scheduling,
+};
+
+/// Cards of one deck, in deck order, together with the deck settings that decide when they are
+/// shown.
+pub struct DeckQueue {
+    pub scheduler: SchedulerKind,
+    pub scheduling: Scheduling,
+    pub cards: Vec<(Card, CardState)>,
+    /// New cards that can still be introduced today
+    pub new_left: i64,
+    /// Review cards that can still be answered today
+    pub reviews_left: i64,
+    /// When set, `scheduling.new_per_day` is the pace the plan needs
+    pub plan: Option<StudyPlan>,
+}
+
+impl DeckQueue {
+    /// Cards left to study today as (new, learning, review), with the daily limits applied
+    pub fn due_counts(&self, now_s: i64) -> (i64, i64, i64) {
+        let count = |pred: &dyn Fn(&CardState) -> bool| {
+            self.cards.iter().filter(|(_, cs)| pred(cs)).count() as i64
+        };
+        let new = count(&|cs| cs.status == CardStatus::New);
+        let learn = count(&|cs| matches!(cs.status, CardStatus::Learn | CardStatus::Relearn));
+        let review = count(&|cs| cs.status == CardStatus::Review && cs.next_review_s <= now_s);
+
+        (
+            new.min(self.new_left.max(0)),
+            learn,
+            review.min(self.reviews_left.max(0)),
+        )
+    }
+}
+
+/// Order in which cards are served within a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Tier {
+    DueLearning,
+    DueReview,
+    New,
+    // Learning cards that are not due yet, but are inside the learn-ahead window. They are pulled
+    // forward so the session does not end while cards are still in learning.
+    LearnAhead,
+}
+
+/// Picks the next card to review: due learning cards first, then due reviews, then new cards in
+/// deck order, and finally learning cards due within the deck learn-ahead window. New cards and
+/// reviews are only served while the deck daily limits allow it. With `ahead_days`, review cards
+/// due within that many study days are served too.
+pub fn next_card(decks: &[DeckQueue], now_s: i64, ahead_days: i64) -> Option<&Card> {
+    decks
+        .iter()
+        .flat_map(|deck| {
+            let learn_ahead_s = deck.scheduling.learn_ahead_minutes * MIN;
+            let rollover_hour = deck.scheduling.day_rollover_hour;
+            let reviews_due_s = now_s.max(day_start_after_s(now_s, ahead_days, rollover_hour));
+            deck.cards
+                .iter()
+                .enumerate()
+                .filter_map(move |(ord, (card, card_state))| {
+                    let due = card_state.next_review_s <= now_s;
+                    let tier = match card_state.status {
+                        CardStatus::Learn | CardStatus::Relearn if due => Tier::DueLearning,
+                        CardStatus::Learn | CardStatus::Relearn
+                            if card_state.next_review_s <= now_s + learn_ahead_s =>
+                        {
+                            Tier::LearnAhead
+                        }
+                        CardStatus::Review
+                            if card_state.next_review_s <= reviews_due_s
+                                && deck.reviews_left > 0 =>
+                        {
+                            Tier::DueReview
+                        }
+                        CardStatus::New if deck.new_left > 0 => Tier::New,
+                        _ => return None,
+                    };
+                    let key = match tier {
+                        Tier::New => ord as i64,
+                        _ => card_state.next_review_s,
+                    };
+                    Some(((tier, key, ord), card))
+                })
+        })
+        .min_by_key(|(key, _)| *key)
+        .map(|(_, card)| card)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        domain::{
+            card::Card,
+            card_state::{CardState, CardStatus},
+            scheduler::SchedulerKind,
+            scheduling::{DEFAULT_DAY_ROLLOVER_HOUR, Scheduling},
+            test_utils::{NOW, card_state},
+        },
+        utils::time_utils::{DAY, MIN, day_start_after_s},
+    };
+
+    use super::{DeckQueue, next_card};
+
+    fn card(id: i64, status: CardStatus, next_review_s: i64) -> (Card, CardState) {
+        (
+            Card {
+                id,
+                ..Default::default()
+            },
+            CardState {
+                card_id: id,
+                ..card_state(status, next_review_s)
+            },
+        )
+    }
+
+    fn deck(cards: Vec<(Card, CardState)>) -> Vec<DeckQueue> {
+        vec![DeckQueue {
+            scheduler: SchedulerKind::Sm2,
+            scheduling: Scheduling::default(),
+            cards,
+            new_left: 20,
+            reviews_left: 200,
+            plan: None,
+        }]
+    }
+
+    fn next_id(decks: &[DeckQueue]) -> Option<i64> {
+        next_card(decks, NOW, 0).map(|card| card.id)
+    }
+
+    #[test]
+    fn test_queue_due_learning_before_reviews_and_new() {
+        let decks = deck(vec![
+            card(1, CardStatus::New, NOW - DAY),
+            card(2, CardStatus::Review, NOW - DAY),
+            card(3, CardStatus::Learn, NOW - MIN),
+        ]);
+        assert_eq!(next_id(&decks), Some(3));
+    }
+
+    #[test]
+    fn test_queue_reviews_before_new() {
+        let decks = deck(vec![
+            card(1, CardStatus::New, NOW - DAY),
+            card(2, CardStatus::Review, NOW - MIN),
+            card(3, CardStatus::Review, NOW + MIN),
+        ]);
+        assert_eq!(next_id(&decks), Some(2));
+    }
+
+    #[test]
+    fn test_queue_new_in_deck_order() {
+        let decks = deck(vec![
+            card(1, CardStatus::New, NOW),
+            card(2, CardStatus::New, NOW - DAY),
+        ]);
+        assert_eq!(next_id(&decks), Some(1));
+    }
+
+    #[test]
+    fn test_queue_learn_ahead_window() {
+        let decks = deck(vec![
+            card(1, CardStatus::Learn, NOW + 10 * MIN),
+            card(2, CardStatus::Review, NOW + MIN),
+        ]);
+        assert_eq!(next_id(&decks), Some(1));
+
+        let decks = deck(vec![card(1, CardStatus::Learn, NOW + DAY)]);
+        assert_eq!(next_id(&decks), None);
+    }
+
+    #[test]
+    fn test_queue_skips_suspended_and_buried() {
+        let decks = deck(vec![
+            card(1, CardStatus::Suspended, NOW - DAY),
+            card(2, CardStatus::Buried, NOW - DAY),
+            card(3, CardStatus::OneTimeLearned, NOW - DAY),
+        ]);
+        assert_eq!(next_id(&decks), None);
+    }
+
+    #[test]
+    fn test_queue_daily_limits() {
+        let mut decks = deck(vec![
+            card(1, CardStatus::Review, NOW - DAY),
+            card(2, CardStatus::New, NOW),
+            card(3, CardStatus::New, NOW),
+        ]);
+        assert_eq!(decks[0].due_counts(NOW), (2, 0, 1));
+
+        decks[0].reviews_left = 0;
+        assert_eq!(next_id(&decks), Some(2));
+
+        decks[0].new_left = 1;
+        assert_eq!(decks[0].due_counts(NOW), (1, 0, 0));
+
+        decks[0].new_left = 0;
+        assert_eq!(next_id(&decks), None);
+    }
+
+    #[test]
+    fn test_review_ahead() {
+        let tomorrow = day_start_after_s(NOW, 1, DEFAULT_DAY_ROLLOVER_HOUR);
+        let decks = deck(vec![card(1, CardStatus::Review, tomorrow)]);
+        assert_eq!(next_id(&decks), None);
+        assert_eq!(next_card(&decks, NOW, 1).map(|card| card.id), Some(1));
+    }
+}
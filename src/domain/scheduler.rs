@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use sqlx::Type;
+
+use crate::utils::time_utils::{MIN, day_start_after_s};
+
+use super::card_state::{CardState, CardStatus, ReviewResult};
+
+// FSRS-4.5 forgetting curve: R(t, S) = (1 + FACTOR * t / S) ^ DECAY, so that R(S, S) = 0.9
+pub const DECAY: f64 = -0.5;
+pub const FACTOR: f64 = 19.0 / 81.0;
+
+/// Probability of recalling a card `elapsed_days` after its last review, given its stability in
+/// days, the time after which recall drops to 90%.
+pub fn forgetting_curve(elapsed_days: f64, stability: f64) -> f64 {
+    (1.0 + FACTOR * elapsed_days / stability.max(0.01)).powf(DECAY)
+}
+
+/// Due time of a review card given an interval of `days`. Review cards are due at the start of a
+/// study day, whatever the time they were answered at.
+pub fn review_due_s(now_s: i64, days: i64, rollover_hour: i64) -> i64 {
+    day_start_after_s(now_s, days, rollover_hour)
+}
+
+/// Moves a lapsed card through the relearning `steps`, in minutes. Returns true once the card is
+/// done relearning, the caller then schedules it as a review card with the interval it got when
+/// it lapsed.
+pub fn relearn(
+    card_state: &mut CardState,
+    review_result: ReviewResult,
+    steps: &[i64],
+    now_s: i64,
+) -> bool {
+    let last_step = steps.len() as i64 - 1;
+    card_state.learning_step = card_state.learning_step.clamp(0, last_step.max(0));
+    let step = match review_result {
+        ReviewResult::Again => 0,
+        ReviewResult::Hard => card_state.learning_step,
+        ReviewResult::Good => card_state.learning_step + 1,
+        ReviewResult::Easy => last_step + 1,
+    };
+    if step > last_step {
+        card_state.status = CardStatus::Review;
+        card_state.learning_step = 0;
+        return true;
+    }
+    card_state.learning_step = step;
+    card_state.next_review_s = now_s + steps[step as usize] * MIN;
+    false
+}
+
+/// Decides when a card is due next, given how it was rated.
+///
+/// `reps`, `lapses` and one-time cards are handled by `CardState::apply_review` before the
+/// scheduler is called, so implementations only have to update the scheduling fields.
+pub trait Scheduler {
+    fn schedule(&self, card_state: &mut CardState, review_result: ReviewResult, now_s: i64);
+
+    /// Schedules a card the review queue served ahead of its due day. Defaults to `schedule`.
+    fn schedule_early(&self, card_state: &mut CardState, review_result: ReviewResult, now_s: i64) {
+        self.schedule(card_state, review_result, now_s);
+    }
+
+    /// Predicted probability of recalling a review card `elapsed_days` after its last review
+    fn recall_probability(&self, card_state: &CardState, elapsed_days: f64) -> f64;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum SchedulerKind {
+    #[default]
+    Sm2,
+    Fsrs,
+}
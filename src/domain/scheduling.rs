@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    fsrs_scheduler::{DEFAULT_DESIRED_RETENTION, DEFAULT_WEIGHTS, FsrsScheduler},
+    scheduler::{Scheduler, SchedulerKind},
+    sm2_scheduler::Sm2Scheduler,
+};
+
+pub const DEFAULT_DAY_ROLLOVER_HOUR: i64 = 4;
+
+/// Per-deck scheduling parameters, read from the optional `[scheduling]` table of the deck file.
+/// Missing keys fall back to the defaults below.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Scheduling {
+    /// Learning steps, in minutes. Passing the last one graduates the card. This and the two
+    /// graduation intervals are for SM-2 only, FSRS has its own learning steps and intervals.
+    pub learning_steps: Vec<i64>,
+    pub graduating_days: i64,
+    /// Days until the first review when a learning card is rated Easy
+    pub easy_days: i64,
+
+    /// Relearning steps for lapsed cards, in minutes. With none, lapsed cards stay in review.
+    pub relearning_steps: Vec<i64>,
+    /// Fraction of the interval kept when a review card is rated Again
+    pub lapse_new_interval: f64,
+    /// Minimum interval, in days, of a card after a lapse
+    pub lapse_min_days: i64,
+
+    pub starting_ease: i64,
+    pub min_ease: i64,
+    pub max_ease: i64,
+    pub again_ease_delta: i64,
+    pub hard_ease_delta: i64,
+    pub easy_ease_delta: i64,
+    /// Interval multiplier for review cards rated Hard
+    pub hard_mult: f64,
+    /// Extra interval multiplier for review cards rated Easy, on top of the ease
+    pub easy_mult: f64,
+    /// Multiplier applied to every review interval
+    pub interval_mult: f64,
+    /// Longest interval, in days, even for cards answered long after they were due
+    pub max_interval_days: i64,
+    /// Share of the days a review card went overdue added to its interval when rated Hard, Good
+    /// or Easy, since it was remembered for that long too
+    pub hard_overdue_share: f64,
+    pub good_overdue_share: f64,
+    pub easy_overdue_share: f64,
+    /// Spread review intervals over a few days around the scheduled one, picking the day with
+    /// the fewest cards due
+    pub fuzz: bool,
+
+    pub desired_retention: f64,
+    pub fsrs_weights: [f64; 17],
+
+    /// Learning cards due within this many minutes are shown instead of ending the session
+    pub learn_ahead_minutes: i64,
+
+    pub new_per_day: i64,
+    pub reviews_per_day: i64,
+    /// Local hour at which a new study day starts. Review cards become due at this hour.
+    pub day_rollover_hour: i64,
+
+    /// Lapses after which a card is considered a leech, 0 to disable
+    pub leech_threshold: i64,
+    pub leech_action: LeechAction,
+
+    /// Solving on the first command within this many seconds suggests Easy
+    pub easy_solve_seconds: i64,
+    /// Solving within this many seconds suggests Good, slower solves suggest Hard
+    pub good_solve_seconds: i64,
+    /// Apply the suggested rating without asking
+    pub auto_grade: bool,
+}
+
+/// What happens to a card when it becomes a leech
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LeechAction {
+    /// Only flag it, so it shows up in `termcards leeches`
+    #[default]
+    Tag,
+    /// Flag it and take it out of the review queue
+    Suspend,
+}
+
+impl Default for Scheduling {
+    fn default() -> Self {
+        Scheduling {
+            learning_steps: vec![1, 10, 24 * 60],
+            graduating_days: 1,
+            easy_days: 4,
+            relearning_steps: vec![10],
+            lapse_new_interval: 0.0,
+            lapse_min_days: 1,
+            starting_ease: 2500,
+            min_ease: 1300,
+            max_ease: 3500,
+            again_ease_delta: 200,
+            hard_ease_delta: 150,
+            easy_ease_delta: 150,
+            hard_mult: 1.2,
+            easy_mult: 1.3,
+            interval_mult: 1.0,
+            max_interval_days: 36500,
+            hard_overdue_share: 0.25,
+            good_overdue_share: 0.5,
+            easy_overdue_share: 1.0,
+            fuzz: true,
+            desired_retention: DEFAULT_DESIRED_RETENTION,
+            fsrs_weights: DEFAULT_WEIGHTS,
+            learn_ahead_minutes: 20,
+            new_per_day: 20,
+            reviews_per_day: 200,
+            day_rollover_hour: DEFAULT_DAY_ROLLOVER_HOUR,
+            leech_threshold: 8,
+            leech_action: LeechAction::Tag,
+            easy_solve_seconds: 10,
+            good_solve_seconds: 60,
+            auto_grade: false,
+        }
+    }
+}
+
+impl Scheduling {
+    /// Checks every parameter is in range and used by the `kind` scheduler, returning what is
+    /// wrong with the first one that isn't
+    pub fn validate(&self, kind: SchedulerKind) -> Result<(), String> {
+        let positive = |value: f64| value > 0.0;
+        let default = Scheduling::default();
+        let checks = [
+            (
+                kind == SchedulerKind::Sm2
+                    || (self.learning_steps == default.learning_steps
+                        && self.graduating_days == default.graduating_days
+                        && self.easy_days == default.easy_days),
+                "learning_steps, graduating_days and easy_days only apply to the sm2 scheduler",
+            ),
+            (
+                self.learning_steps.iter().all(|&step| step > 0),
+                "learning_steps must be positive",
+            ),
+            (
+                self.relearning_steps.iter().all(|&step| step > 0),
+                "relearning_steps must be positive",
+            ),
+            (self.graduating_days > 0, "graduating_days must be positive"),
+            (self.easy_days > 0, "easy_days must be positive"),
+            (
+                (0.0..=1.0).contains(&self.lapse_new_interval),
+                "lapse_new_interval must be between 0 and 1",
+            ),
+            (self.lapse_min_days >= 0, "lapse_min_days can't be negative"),
+            (
+                0 < self.min_ease
+                    && self.min_ease <= self.starting_ease
+                    && self.starting_ease <= self.max_ease,
+                "min_ease, starting_ease and max_ease must be positive and in that order",
+            ),
+            (positive(self.hard_mult), "hard_mult must be positive"),
+            (positive(self.easy_mult), "easy_mult must be positive"),
+            (
+                positive(self.interval_mult),
+                "interval_mult must be positive",
+            ),
+            (
+                self.max_interval_days > 0,
+                "max_interval_days must be positive",
+            ),
+            (
+                [
+                    self.hard_overdue_share,
+                    self.good_overdue_share,
+                    self.easy_overdue_share,
+                ]
+                .iter()
+                .all(|share| (0.0..=1.0).contains(share)),
+                "overdue shares must be between 0 and 1",
+            ),
+            (
+                positive(self.desired_retention) && self.desired_retention < 1.0,
+                "desired_retention must be between 0 and 1",
+            ),
+            (
+                self.fsrs_weights.iter().all(|weight| weight.is_finite()),
+                "fsrs_weights must be numbers",
+            ),
+            (
+                self.learn_ahead_minutes >= 0,
+                "learn_ahead_minutes can't be negative",
+            ),
+            (self.new_per_day >= 0, "new_per_day can't be negative"),
+            (
+                self.reviews_per_day >= 0,
+                "reviews_per_day can't be negative",
+            ),
+            (
+                (0..24).contains(&self.day_rollover_hour),
+                "day_rollover_hour must be between 0 and 23",
+            ),
+            (
+                self.leech_threshold >= 0,
+                "leech_threshold can't be negative",
+            ),
+            (
+                0 <= self.easy_solve_seconds && self.easy_solve_seconds <= self.good_solve_seconds,
+                "easy_solve_seconds can't be negative or above good_solve_seconds",
+            ),
+        ];
+        match checks.into_iter().find(|(valid, _)| !valid) {
+            Some((_, error)) => Err(error.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    pub fn scheduler(&self, kind: SchedulerKind) -> Box<dyn Scheduler> {
+        match kind {
+            SchedulerKind::Sm2 => Box::new(Sm2Scheduler {
+                scheduling: self.clone(),
+            }),
+            SchedulerKind::Fsrs => Box::new(FsrsScheduler::new(self)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::domain::scheduler::SchedulerKind;
+
+    use super::Scheduling;
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Scheduling::default().validate(SchedulerKind::Sm2), Ok(()));
+        assert_eq!(Scheduling::default().validate(SchedulerKind::Fsrs), Ok(()));
+
+        let invalid = [
+            Scheduling {
+                desired_retention: 1.0,
+                ..Default::default()
+            },
+            Scheduling {
+                desired_retention: f64::NAN,
+                ..Default::default()
+            },
+            Scheduling {
+                day_rollover_hour: 24,
+                ..Default::default()
+            },
+            Scheduling {
+                learning_steps: vec![1, 0],
+                ..Default::default()
+            },
+            Scheduling {
+                hard_mult: -1.2,
+                ..Default::default()
+            },
+            Scheduling {
+                max_interval_days: 0,
+                ..Default::default()
+            },
+        ];
+        for scheduling in invalid {
+            assert!(
+                scheduling.validate(SchedulerKind::Sm2).is_err(),
+                "{scheduling:?}"
+            );
+        }
+
+        // FSRS does not use them, so they can't be changed
+        let learning = Scheduling {
+            easy_days: 2,
+            ..Default::default()
+        };
+        assert_eq!(learning.validate(SchedulerKind::Sm2), Ok(()));
+        assert!(learning.validate(SchedulerKind::Fsrs).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::utils::time_utils::MIN;
+
+use super::{
+    card_state::{CardState, CardStatus, ReviewResult},
+    scheduler::{Scheduler, forgetting_curve, relearn, review_due_s},
+    scheduling::Scheduling,
+};
+
+/// Anki-style SM-2 scheduler: fixed learning steps, then intervals grown by the card ease.
+#[derive(Debug, Default)]
+pub struct Sm2Scheduler {
+    pub scheduling: Scheduling,
+}
+
+impl Sm2Scheduler {
+    fn learning_interval(&self, step: i64) -> i64 {
+        self.scheduling
+            .learning_steps
+            .get(step as usize)
+            .map_or(MIN, |minutes| minutes * MIN)
+    }
+
+    /// Interval after a successful review, grown by `mult`. Overdue days count by
+    /// `overdue_share`, since the card was remembered for that long too. Cards reviewed `early`,
+    /// ahead of their due day, only grow the days elapsed, but keep at least `early_min_share`
+    /// of the current interval.
+    fn grown_interval(
+        &self,
+        card_state: &CardState,
+        now_s: i64,
+        early: bool,
+        mult: f64,
+        overdue_share: f64,
+        early_min_share: f64,
+    ) -> f64 {
+        let interval = card_state.interval_days as f64;
+        let days_late = card_state.days_late(now_s, self.scheduling.day_rollover_hour) as f64;
+        if early && days_late < 0.0 {
+            let elapsed = (interval + days_late).max(0.0);
+            (elapsed * mult).max(interval * early_min_share)
+        } else {
+            (interval + days_late.max(0.0) * overdue_share) * mult
+        }
+    }
+
+    fn schedule_review(
+        &self,
+        card_state: &mut CardState,
+        review_result: ReviewResult,
+        now_s: i64,
+        early: bool,
+    ) {
+        let s = &self.scheduling;
+        let last_learning_step = s.learning_steps.len() as i64 - 1;
+        card_state.learning_step = card_state.learning_step.clamp(0, last_learning_step.max(0));
+
+        match card_state.status {
+            // Learning
+            CardStatus::New | CardStatus::Learn => {
+                card_state.status = CardStatus::Learn;
+                match review_result {
+                    ReviewResult::Again => {
+                        card_state.learning_step = 0;
+                        card_state.next_review_s = now_s + self.learning_interval(0);
+                    }
+                    ReviewResult::Hard => {
+                        card_state.next_review_s =
+                            now_s + self.learning_interval(card_state.learning_step);
+                    }
+                    ReviewResult::Good => {
+                        card_state.learning_step += 1;
+                        if card_state.learning_step > last_learning_step {
+                            card_state.status = CardStatus::Review;
+                            card_state.learning_step = 0;
+                            card_state.interval_days = s.graduating_days;
+                            card_state.next_review_s =
+                                review_due_s(now_s, s.graduating_days, s.day_rollover_hour);
+                        } else {
+                            card_state.next_review_s =
+                                now_s + self.learning_interval(card_state.learning_step);
+                        }
+                    }
+                    ReviewResult::Easy => {
+                        card_state.next_review_s =
+                            review_due_s(now_s, s.easy_days, s.day_rollover_hour);
+                        card_state.interval_days = s.easy_days;
+                        card_state.status = CardStatus::Review;
+                    }
+                }
+            }
+            // Relearning, the interval was already cut down when the card lapsed
+            CardStatus::Relearn => {
+                if relearn(card_state, review_result, &s.relearning_steps, now_s) {
+                    card_state.next_review_s =
+                        review_due_s(now_s, card_state.interval_days, s.day_rollover_hour);
+                }
+            }
+            // Reviewing
+            CardStatus::Review => match review_result {
+                ReviewResult::Again => {
+                    card_state.ease = (card_state.ease - s.again_ease_delta).max(s.min_ease);
+                    card_state.interval_days =
+                        ((card_state.interval_days as f64 * s.lapse_new_interval) as i64)
+                            .max(s.lapse_min_days)
+                            .max(1);
+                    card_state.status = CardStatus::Relearn;
+                    card_state.learning_step = 0;
+                    if relearn(card_state, ReviewResult::Again, &s.relearning_steps, now_s) {
+                        card_state.next_review_s =
+                            review_due_s(now_s, card_state.interval_days, s.day_rollover_hour);
+                    }
+                }
+                ReviewResult::Hard => {
+                    card_state.ease = (card_state.ease - s.hard_ease_delta).max(s.min_ease);
+                    let mult = s.hard_mult * s.interval_mult;
+                    let interval = self.grown_interval(
+                        card_state,
+                        now_s,
+                        early,
+                        mult,
+                        s.hard_overdue_share,
+                        s.hard_mult / 2.0,
+                    );
+                    card_state.interval_days = self.capped(interval).max(1);
+                    card_state.next_review_s =
+                        review_due_s(now_s, card_state.interval_days, s.day_rollover_hour);
+                }
+                ReviewResult::Good => {
+                    let mult = card_state.ease as f64 / 1000.0 * s.interval_mult;
+                    let interval = self.grown_interval(
+                        card_state,
+                        now_s,
+                        early,
+                        mult,
+                        s.good_overdue_share,
+                        1.0,
+                    );
+                    card_state.interval_days = self.capped(interval).max(1);
+                    card_state.next_review_s =
+                        review_due_s(now_s, card_state.interval_days, s.day_rollover_hour);
+                }
+                ReviewResult::Easy => {
+                    card_state.ease = (card_state.ease + s.easy_ease_delta).min(s.max_ease);
+                    let mult = card_state.ease as f64 / 1000.0 * s.easy_mult * s.interval_mult;
+                    let interval = self.grown_interval(
+                        card_state,
+                        now_s,
+                        early,
+                        mult,
+                        s.easy_overdue_share,
+                        1.0,
+                    );
+                    card_state.interval_days = self.capped(interval);
+                    card_state.next_review_s =
+                        review_due_s(now_s, card_state.interval_days, s.day_rollover_hour);
+                }
+            },
+            CardStatus::OneTimeLearned | CardStatus::Suspended | CardStatus::Buried => {}
+        }
+    }
+
+    fn capped(&self, interval_days: f64) -> i64 {
+        (interval_days as i64).min(self.scheduling.max_interval_days)
+    }
+}
+
+impl Scheduler for Sm2Scheduler {
+    fn schedule(&self, card_state: &mut CardState, review_result: ReviewResult, now_s: i64) {
+        self.schedule_review(card_state, review_result, now_s, false);
+    }
+
+    fn schedule_early(&self, card_state: &mut CardState, review_result: ReviewResult, now_s: i64) {
+        self.schedule_review(card_state, review_result, now_s, true);
+    }
+
+    /// SM-2 has no memory model, so the scheduled interval is taken as the stability: reviews
+    /// are expected to be answered correctly 90% of the time when done on time.
+    fn recall_probability(&self, card_state: &CardState, elapsed_days: f64) -> f64 {
+        forgetting_curve(elapsed_days, card_state.interval_days.max(1) as f64)
+    }
+}
@@ -0,0 +1,12 @@
+use super::card_state::{CardState, CardStatus};
+
+/// Time the tests run at
+pub const NOW: i64 = 1_700_000_000;
+
+/// State of a card in `status` due at `next_review_s`, otherwise as `CardState::new` makes it
+pub fn card_state(status: CardStatus, next_review_s: i64) -> CardState {
+    CardState {
+        status,
+        ..CardState::new(0, next_review_s, 2500)
+    }
+}
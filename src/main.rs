@@ -1,103 +1,469 @@
-use args::{Args, Commands};
+use args::{Args, CardCommands, Commands};
 use clap::Parser;
-use crossterm::style::Stylize;
-use domain::{card::Card, card_state::{CardStatus, CardState}};
+use crossterm::style::{StyledContent, Stylize};
+use domain::{
+    card_state::CardStatus,
+    cram::{CramCard, CramFilter},
+    deck_update::UpdateSummary,
+    forecast::ForecastDay,
+    leech::Leech,
+    optimizer::Fit,
+    review_log::{RATING_FORGET, RATING_POSTPONE, RATING_SET_DUE, ReviewLog},
+    review_queue::DeckQueue,
+    scheduler::SchedulerKind,
+};
 use repository::repository::Repository;
-use service::service::Service;
-use utils::time_utils::{format_until_duration, now_s};
+use service::{review_log_service::Undone, service::Service};
+use utils::{
+    clock::Clock,
+    time_utils::{format_date, format_timestamp, format_until_duration, set_timezone},
+};
 
 mod args;
 
 mod domain {
+    pub mod attempt;
     pub mod card;
     pub mod card_state;
+    pub mod cram;
     pub mod deck;
+    pub mod deck_update;
+    pub mod due_date;
+    pub mod forecast;
+    pub mod fsrs_scheduler;
+    pub mod leech;
+    pub mod load_balance;
+    pub mod optimizer;
+    pub mod package;
+    pub mod plan;
+    pub mod review_log;
+    pub mod review_queue;
+    pub mod scheduler;
+    pub mod scheduling;
+    pub mod sm2_scheduler;
+    #[cfg(test)]
+    pub mod test_utils;
 }
 
 mod repository {
+    pub mod card_repository;
     pub mod deck_repository;
+    #[allow(clippy::module_inception)]
     pub mod repository;
+    pub mod review_log_repository;
     pub mod review_repository;
 }
 
 mod service {
+    pub mod card_service;
     pub mod deck_service;
+    pub mod forecast_service;
+    pub mod optimizer_service;
+    pub mod package_service;
+    pub mod review_log_service;
     pub mod review_service;
     pub mod scheduler_service;
+    #[allow(clippy::module_inception)]
     pub mod service;
+    #[cfg(test)]
+    pub mod test_utils;
 }
 
 mod utils {
+    pub mod clock;
+    pub mod data_dir;
+    pub mod fixture_dir;
+    pub mod path_utils;
+    pub mod temp_dir;
     pub mod time_utils;
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    // Changes the environment, so it must run before the runtime starts its threads
+    if let Some(timezone) = &args.timezone {
+        set_timezone(timezone).map_err(anyhow::Error::msg)?;
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(args))
+}
 
-    let repository = Repository::new().await?;
-    let service = Service::new(repository);
+async fn run(args: Args) -> anyhow::Result<()> {
+    let clock = args.now.map_or(Clock::system(), Clock::starting_at);
+    let repository = Repository::new(clock).await?;
+    let service = Service::new(repository, clock);
 
     match args.command {
-        Commands::Import { path } => {
-            service.import_deck(path).await?;
+        Commands::Import {
+            path,
+            update,
+            retire,
+        } => {
+            if let Some(summary) = service.import_deck(path, update, retire).await? {
+                print_update_summary(summary, retire);
+            }
         }
-        Commands::Review { deck_name } => service.review(deck_name).await?,
-        Commands::State { deck_name } => {
-            print_deck_state(&deck_name, service.get_deck_state(&deck_name).await?)
+        Commands::Review { deck_name, ahead } => {
+            service.review(deck_name, ahead.unwrap_or(0)).await?
+        }
+        Commands::State { deck_name } => print_deck_state(
+            &deck_name,
+            service.get_deck_queue(&deck_name).await?,
+            clock.now_s(),
+        ),
+        Commands::History { deck_name, card } => print_history(
+            &deck_name,
+            service.get_review_history(&deck_name, card).await?,
+        ),
+        Commands::Forecast { deck_name, days } => print_forecast(
+            service
+                .get_forecast(deck_name.as_deref().unwrap_or(""), days as usize)
+                .await?,
+        ),
+        Commands::Undo => match service.undo_last_review().await? {
+            Some(Undone::Review {
+                deck_name,
+                ord,
+                review_log,
+            }) => match manual_change_name(review_log.rating) {
+                Some(change) => println!("Undid {} of {} card {}", change, deck_name.bold(), ord),
+                None => println!(
+                    "Undid rating of {} card {}: {}",
+                    deck_name.bold(),
+                    ord,
+                    rating_str(review_log.rating)
+                ),
+            },
+            Some(Undone::Postpone { cards }) => println!("Undid postponing {cards} cards"),
+            None => println!("Nothing to undo"),
+        },
+        Commands::Leeches { deck_name } => print_leeches(
+            service
+                .get_leeches(deck_name.as_deref().unwrap_or(""))
+                .await?,
+        ),
+        Commands::Optimize { deck_name, write } => print_optimize(
+            service
+                .optimize_decks(deck_name.as_deref().unwrap_or(""), write)
+                .await?,
+            write,
+        ),
+        Commands::Suspend { deck_name, card } => service.suspend_card(&deck_name, card).await?,
+        Commands::Unsuspend { deck_name, card } => service.unsuspend_card(&deck_name, card).await?,
+        Commands::Bury { deck_name, card } => service.bury_card(&deck_name, card).await?,
+        Commands::Postpone {
+            deck_name, days, ..
+        } => print_postponed(
+            service
+                .postpone_reviews(deck_name.as_deref().unwrap_or(""), days)
+                .await?,
+            days,
+        ),
+        Commands::Plan {
+            deck_name,
+            finish_by,
+            ..
+        } => {
+            service.set_study_plan(&deck_name, finish_by).await?;
+            print_deck_state(
+                &deck_name,
+                service.get_deck_queue(&deck_name).await?,
+                clock.now_s(),
+            )
+        }
+        Commands::Card { command } => match command {
+            CardCommands::Forget { deck_name, card } => {
+                service.forget_card(&deck_name, card).await?
+            }
+            CardCommands::SetDue {
+                deck_name,
+                card,
+                due,
+            } => service.set_card_due(&deck_name, card, due).await?,
+        },
+        Commands::Cram {
+            deck_name,
+            tag,
+            status,
+            shuffle,
+            requeue,
+        } => {
+            let filter = CramFilter { tag, status };
+            print_cram_summary(
+                &deck_name,
+                service.cram(&deck_name, &filter, shuffle, requeue).await?,
+            )
+        }
+        Commands::ExportPackage { deck_name, output } => {
+            let path = service.export_package(&deck_name, output).await?;
+            println!("Exported {} to {}", deck_name.bold(), path.display());
         }
         Commands::TestDeck { path } => {
             let deck = service.read_deck_from_file(path)?;
-            service.review_full_deck(deck);
+            let deck_name = deck.name.clone();
+            print_cram_summary(&deck_name, service.test_deck(deck)?);
         }
     };
     Ok(())
 }
 
-fn print_deck_state(deck_name: &str, card_state_list: Vec<(Card, CardState)>) {
+fn print_update_summary(summary: UpdateSummary, retired: bool) {
+    let mut line = format!("{} changed, {} added", summary.changed, summary.added);
+    if summary.removed > 0 {
+        line += &if retired {
+            format!(", {} retired", summary.removed)
+        } else {
+            format!(", {} no longer in the file kept", summary.removed)
+        };
+    }
+    println!("{line}");
+}
+
+fn print_deck_state(deck_name: &str, deck_queue: DeckQueue, now_s: i64) {
+    let card_state_list = &deck_queue.cards;
+    let rollover_hour = deck_queue.scheduling.day_rollover_hour;
     if card_state_list.is_empty() {
         println!("No cards found");
         return;
     }
-    let new = card_state_list
-        .iter()
-        .filter(|cs| cs.1.status == CardStatus::New)
-        .count();
-    let learn = card_state_list
-        .iter()
-        .filter(|cs| cs.1.status == CardStatus::Learn)
-        .count();
-    let to_review = card_state_list
-        .iter()
-        .filter(|cs| cs.1.status == CardStatus::Review && cs.1.next_review_s < now_s())
-        .count();
-    let total_cards = card_state_list.iter().count();
+    // Counts left for today, capped by the deck daily limits
+    let (new, learn, to_review) = deck_queue.due_counts(now_s);
+    let total_cards = card_state_list.len();
 
     println!(
         "{}   {} {} {}    {}",
-        format!("{deck_name}").bold(),
+        deck_name.bold(),
         format!("{new:>4}").bold().blue(),
         format!("{learn:>4}").bold().red(),
         format!("{to_review:>4}").bold().green(),
         format!("Total cards: {total_cards}").dark_grey(),
     );
+    if let Some(plan) = deck_queue.plan {
+        println!(
+            "    {}",
+            format!(
+                "Plan: {} new per day to finish by {}, {} days left",
+                deck_queue.scheduling.new_per_day,
+                format_date(plan.finish_by_s),
+                plan.days_left(now_s, rollover_hour),
+            )
+            .dark_grey()
+        );
+    }
 
-    for (i, (_, card_state)) in card_state_list.iter().enumerate() {
+    for (i, (card, card_state)) in card_state_list.iter().enumerate() {
         let status_str = match card_state.status {
             CardStatus::New => "New".to_string().blue().bold(),
             CardStatus::Learn => "Learn".to_string().red().bold(),
+            CardStatus::Relearn => "Relearn".to_string().red().bold(),
             CardStatus::Review => {
-                if card_state.next_review_s < now_s() {
+                if card_state.next_review_s < now_s {
                     "Review".to_string().green().bold()
                 } else {
-                    format_until_duration(card_state.next_review_s - now_s()).dark_grey()
+                    format_until_duration(card_state.next_review_s, now_s, rollover_hour)
+                        .dark_grey()
                 }
-            },
+            }
+            CardStatus::Suspended => "Suspended".to_string().yellow().bold(),
+            CardStatus::Buried => "Buried".to_string().dark_grey().bold(),
             CardStatus::OneTimeLearned => continue,
         };
+        match &card.key {
+            Some(key) => println!("    {} {} {}", i, status_str, key.clone().dark_grey()),
+            None => println!("    {} {}", i, status_str),
+        }
+    }
+}
+
+fn rating_str(rating: i64) -> StyledContent<String> {
+    match rating {
+        1 => format!("{:<5}", "Again").red().bold(),
+        2 => format!("{:<5}", "Hard").yellow().bold(),
+        3 => format!("{:<5}", "Good").green().bold(),
+        4 => format!("{:<5}", "Easy").blue().bold(),
+        RATING_FORGET => format!("{:<5}", "Reset").magenta(),
+        RATING_SET_DUE => format!("{:<5}", "Moved").magenta(),
+        RATING_POSTPONE => format!("{:<5}", "Delay").magenta(),
+        rating => format!("{rating:<5}").dark_grey(),
+    }
+}
+
+/// Command that made a manual change logged as a rating, `None` for real ratings
+fn manual_change_name(rating: i64) -> Option<&'static str> {
+    match rating {
+        RATING_FORGET => Some("forget"),
+        RATING_SET_DUE => Some("set-due"),
+        RATING_POSTPONE => Some("postpone"),
+        _ => None,
+    }
+}
+
+fn print_history(deck_name: &str, history: Vec<(i64, ReviewLog)>) {
+    if history.is_empty() {
+        println!("No reviews found");
+        return;
+    }
+
+    println!("{}", deck_name.bold());
+    for (ord, log) in history {
+        println!(
+            "    {}  {:>4}  {}  {:>4}d -> {:>4}d  {:>4} -> {:>4}  {}  {}",
+            format_timestamp(log.timestamp).dark_grey(),
+            ord,
+            rating_str(log.rating),
+            log.prev_ivl,
+            log.new_ivl,
+            log.prev_ease,
+            log.new_ease,
+            format!("{:>6.1}s", log.duration_ms as f64 / 1000.0).dark_grey(),
+            if log.revealed {
+                "revealed".to_string().yellow()
+            } else {
+                format!("{:>3} cmd", log.commands).dark_grey()
+            },
+        );
+    }
+}
+
+const FORECAST_BAR_WIDTH: i64 = 50;
+
+fn print_forecast(forecast: Vec<ForecastDay>) {
+    let max = forecast.iter().map(ForecastDay::total).max().unwrap_or(0);
+    // Only shrink the bars when the busiest day would not fit
+    let scale = |count: i64| {
+        if max <= FORECAST_BAR_WIDTH {
+            count as usize
+        } else {
+            ((count * FORECAST_BAR_WIDTH + max - 1) / max) as usize
+        }
+    };
+
+    println!(
+        "{}  {}  {}",
+        "# review".to_string().green(),
+        "+ learn".to_string().red(),
+        "* new".to_string().blue(),
+    );
+    for (i, day) in forecast.iter().enumerate() {
+        let label = match i {
+            0 => "Today".to_string(),
+            1 => "Tomorrow".to_string(),
+            _ => format_date(day.start_s),
+        };
+        println!(
+            "{:>10} {:>4} {}{}{}",
+            label,
+            day.total(),
+            "#".repeat(scale(day.review)).green(),
+            "+".repeat(scale(day.learn)).red(),
+            "*".repeat(scale(day.new)).blue(),
+        );
+    }
+}
+
+fn print_leeches(leeches: Vec<Leech>) {
+    if leeches.is_empty() {
+        println!("No leeches found");
+        return;
+    }
+
+    let mut deck_name = "";
+    for leech in &leeches {
+        if leech.deck_name != deck_name {
+            deck_name = &leech.deck_name;
+            println!("{}", deck_name.bold());
+        }
+        let status_str = if leech.status == CardStatus::Suspended {
+            format!("{:<9}", "Suspended").yellow().bold()
+        } else {
+            format!("{:<9}", "Active").dark_grey()
+        };
+        println!(
+            "    {:>4}  {}  {}  {}",
+            leech.ord,
+            format!("{:>3} lapses", leech.lapses).red(),
+            status_str,
+            leech.expected_input,
+        );
+    }
+}
+
+fn print_optimize(fits: Vec<(String, SchedulerKind, Option<Fit>)>, written: bool) {
+    for (deck_name, scheduler, fit) in fits {
+        let scheduler = match scheduler {
+            SchedulerKind::Sm2 => "sm2",
+            SchedulerKind::Fsrs => "fsrs",
+        };
+        println!(
+            "{} {}",
+            deck_name.bold(),
+            format!("({scheduler})").dark_grey()
+        );
+        let Some(fit) = fit else {
+            println!(
+                "    {}",
+                "Not enough review history".to_string().dark_grey()
+            );
+            continue;
+        };
+        println!(
+            "    {} reviews, actual retention {:.1}%",
+            fit.before.reviews,
+            fit.before.actual_retention * 100.0
+        );
+        println!(
+            "    predicted retention  {:>6.1}% -> {}",
+            fit.before.predicted_retention * 100.0,
+            format!("{:.1}%", fit.after.predicted_retention * 100.0).bold()
+        );
+        println!(
+            "    log loss             {:>7.4} -> {}",
+            fit.before.log_loss,
+            format!("{:.4}", fit.after.log_loss).bold()
+        );
+        if written {
+            println!(
+                "    {}",
+                "Saved to the deck scheduling settings".to_string().green()
+            );
+        }
+    }
+}
+
+fn print_postponed(postponed: Vec<(String, usize)>, days: i64) {
+    for (deck_name, count) in postponed {
+        println!(
+            "{}   {}",
+            deck_name.bold(),
+            format!("{count} reviews moved past the next {days} days").dark_grey()
+        );
+    }
+}
+
+fn print_cram_summary(deck_name: &str, results: Vec<CramCard>) {
+    if results.is_empty() {
+        println!("No cards found");
+        return;
+    }
+
+    let failed: Vec<&CramCard> = results.iter().filter(|card| card.failures > 0).collect();
+    println!(
+        "{}   {} {}",
+        deck_name.bold(),
+        format!("{} passed", results.len() - failed.len())
+            .green()
+            .bold(),
+        format!("{} failed", failed.len()).red().bold(),
+    );
+    for card in failed {
         println!(
-            "    {} {}",
-            i, status_str
-        )
+            "    {:>4}  {}  {}",
+            card.ord,
+            format!("{}x", card.failures).red(),
+            card.expected_input.clone().dark_grey(),
+        );
     }
 }
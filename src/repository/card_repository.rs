@@ -0,0 +1,160 @@
+use sqlx::SqliteExecutor;
+
+use crate::domain::{card::CardRef, leech::Leech};
+
+use super::repository::{Repository, RepositoryError};
+
+impl Repository {
+    /// Id of a card of a deck, given by its position as shown by `termcards state` or its key
+    pub async fn get_card_id(
+        &self,
+        deck_name: &str,
+        card: &CardRef,
+    ) -> Result<i64, RepositoryError> {
+        let (ord, key) = match card {
+            CardRef::Ord(ord) => (Some(*ord), None),
+            CardRef::Key(key) => (None, Some(key.as_str())),
+        };
+        sqlx::query_scalar!(
+            r#"
+                SELECT id as "id!: i64"
+                FROM cards
+                WHERE deck_name = ? AND NOT retired AND (ord = ? OR key = ?)
+            "#,
+            deck_name,
+            ord,
+            key
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            let card = match card {
+                CardRef::Ord(ord) => ord.to_string(),
+                CardRef::Key(key) => key.clone(),
+            };
+            RepositoryError::NotFound("card".to_string(), format!("{deck_name} {card}"))
+        })
+    }
+
+    /// Deck and position in the deck of a card
+    pub async fn get_card_position(&self, card_id: i64) -> Result<(String, i64), RepositoryError> {
+        let res = sqlx::query!(
+            r#"
+                SELECT deck_name, ord
+                FROM cards
+                WHERE id = ?
+            "#,
+            card_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RepositoryError::NotFound("card".to_string(), card_id.to_string()))?;
+
+        Ok((res.deck_name, res.ord))
+    }
+
+    pub async fn get_leeches(&self, prefix: &str) -> Result<Vec<Leech>, RepositoryError> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT
+                    cards.deck_name,
+                    cards.ord,
+                    cards.expected_input,
+                    card_state.lapses,
+                    card_state.status
+                FROM cards
+                INNER JOIN card_state ON card_state.card_id = cards.id
+                WHERE
+                    cards.deck_name LIKE ? || '%'
+                    AND NOT cards.retired
+                    AND card_state.leech
+                ORDER BY cards.deck_name, card_state.lapses DESC, cards.ord
+            "#,
+            prefix
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Leech {
+                deck_name: row.deck_name,
+                ord: row.ord,
+                lapses: row.lapses,
+                status: row.status.into(),
+                expected_input: row.expected_input,
+            })
+            .collect())
+    }
+
+    pub async fn suspend_card(&self, card_id: i64) -> Result<(), RepositoryError> {
+        suspend_card(&self.pool, card_id).await
+    }
+
+    pub async fn bury_card(&self, card_id: i64, until_s: i64) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+                UPDATE card_state
+                SET
+                    prev_status = CASE WHEN status = -2 THEN prev_status ELSE status END,
+                    status = -2,
+                    buried_until_s = ?
+                WHERE card_id = ? AND status != -1
+            "#,
+            until_s,
+            card_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Puts a suspended or buried card back in its previous status, or New once it was forgotten
+    pub async fn unsuspend_card(&self, card_id: i64) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+                UPDATE card_state
+                SET status = CASE WHEN reps = 0 THEN 0 ELSE prev_status END
+                WHERE card_id = ? AND status IN (-1, -2)
+            "#,
+            card_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn unbury_expired(&self, now_s: i64) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+                UPDATE card_state
+                SET status = CASE WHEN reps = 0 THEN 0 ELSE prev_status END
+                WHERE status = -2 AND buried_until_s <= ?
+            "#,
+            now_s
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Suspends a card, remembering the status it goes back to when unsuspended
+pub(in crate::repository) async fn suspend_card<'e, E: SqliteExecutor<'e>>(
+    executor: E,
+    card_id: i64,
+) -> Result<(), RepositoryError> {
+    sqlx::query!(
+        r#"
+            UPDATE card_state
+            SET
+                prev_status = CASE WHEN status IN (-1, -2) THEN prev_status ELSE status END,
+                status = -1
+            WHERE card_id = ?
+        "#,
+        card_id
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use crate::domain::{card::Card, deck::Deck};
+use crate::domain::{
+    card::Card, deck::Deck, deck_update::CardMatches, plan::StudyPlan, scheduler::SchedulerKind,
+    scheduling::Scheduling,
+};
 use sqlx::{Sqlite, Transaction};
 
 use super::repository::{Repository, RepositoryError};
@@ -9,49 +12,125 @@ impl Repository {
         sqlx::query(
             r#"
             INSERT INTO decks
-                (name, description)
+                (name, description, scheduler, scheduling)
             VALUES
-                (?,    ?)
+                (?,    ?,           ?,         ?)
             "#,
         )
         .bind(&deck.name)
         .bind(&deck.description)
+        .bind(deck.scheduler)
+        .bind(serde_json::to_string(&deck.scheduling).unwrap())
         .execute(&mut *tx)
         .await?;
 
-        let mut ord = 0;
-        for card in deck.cards.iter_mut() {
-            card.id = sqlx::query_scalar(r#"
-                INSERT INTO cards
-                    (deck_name, ord, expected_output, expected_input, command, docker_image, work_dir, volume_mounts, one_time)
-                VALUES
-                    (?,         ?,   ?,               ?,              ?,       ?,            ?,        ?,             ?)
-                RETURNING id
-                "#)
-                .bind(&deck.name)
-                .bind(ord)
-                .bind(&card.expected_output)
-                .bind(&card.expected_input)
-                .bind(&card.command)
-                .bind(&card.docker_image)
-                .bind(&card.work_dir)
-                .bind(serde_json::to_string(&card.volume_mounts).unwrap())
-                .bind(&card.one_time)
-                .fetch_one(&mut *tx)
-                .await?;
-
-            sqlx::query(
-                r#"
-                INSERT INTO card_state 
-                    (card_id)
-                VALUES
-                    (?)
+        let now = self.clock.now_s();
+        for (ord, card) in deck.cards.iter_mut().enumerate() {
+            card.id = insert_card(&mut tx, &deck.name, ord as i64, card).await?;
+            insert_card_state(&mut tx, card.id, deck.scheduling.starting_ease, now).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(deck)
+    }
+
+    /// Updates a stored deck from its file. Cards with an id in `matches` are updated in place,
+    /// keeping their state and history, the others are added. Cards in `removed` are retired when
+    /// `retire` is set, or else kept after the cards of the file.
+    pub async fn update_deck(
+        &self,
+        mut deck: Deck,
+        matches: &CardMatches,
+        retire: bool,
+    ) -> Result<Deck, RepositoryError> {
+        let mut tx: Transaction<'_, Sqlite> = self.pool.begin().await?;
+        let now = self.clock.now_s();
+        let scheduling = serde_json::to_string(&deck.scheduling).unwrap();
+        sqlx::query!(
+            r#"
+            UPDATE decks
+            SET description = ?, scheduler = ?, scheduling = ?, updated_at = ?
+            WHERE name = ?
             "#,
+            deck.description,
+            deck.scheduler,
+            scheduling,
+            now,
+            deck.name
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (ord, (card, matched)) in deck.cards.iter_mut().zip(&matches.matches).enumerate() {
+            let ord = ord as i64;
+            let Some(card_id) = *matched else {
+                card.id = insert_card(&mut tx, &deck.name, ord, card).await?;
+                insert_card_state(&mut tx, card.id, deck.scheduling.starting_ease, now).await?;
+                continue;
+            };
+            card.id = card_id;
+            let volume_mounts = serde_json::to_string(&card.volume_mounts).unwrap();
+            let tags = serde_json::to_string(&card.tags).unwrap();
+            let files = serde_json::to_string(&card.files).unwrap();
+            sqlx::query!(
+                r#"
+                UPDATE cards
+                SET
+                    ord = ?,
+                    key = ?,
+                    expected_output = ?,
+                    expected_input = ?,
+                    command = ?,
+                    docker_image = ?,
+                    work_dir = ?,
+                    volume_mounts = ?,
+                    one_time = ?,
+                    tags = ?,
+                    files = ?,
+                    updated_at = ?
+                WHERE id = ?
+                "#,
+                ord,
+                card.key,
+                card.expected_output,
+                card.expected_input,
+                card.command,
+                card.docker_image,
+                card.work_dir,
+                volume_mounts,
+                card.one_time,
+                tags,
+                files,
+                now,
+                card_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for (i, card_id) in matches.removed.iter().enumerate() {
+            // Retired cards leave the deck order and give up their id, so both can be taken by new
+            // cards
+            let ord = if retire {
+                -1
+            } else {
+                (deck.cards.len() + i) as i64
+            };
+            sqlx::query!(
+                r#"
+                UPDATE cards
+                SET ord = ?, key = CASE WHEN ? THEN NULL ELSE key END, retired = ?, updated_at = ?
+                WHERE id = ?
+                "#,
+                ord,
+                retire,
+                retire,
+                now,
+                card_id
             )
-            .bind(card.id)
             .execute(&mut *tx)
             .await?;
-            ord += 1;
         }
 
         tx.commit().await?;
@@ -59,11 +138,100 @@ impl Repository {
         Ok(deck)
     }
 
+    pub async fn get_deck_names(&self, prefix: &str) -> Result<Vec<String>, RepositoryError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT name
+            FROM decks
+            WHERE name LIKE ? || '%'
+            ORDER BY name
+            "#,
+            prefix
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.into())
+    }
+
+    pub async fn set_deck_scheduling(
+        &self,
+        deck_name: &str,
+        scheduling: &Scheduling,
+    ) -> Result<(), RepositoryError> {
+        let scheduling = serde_json::to_string(scheduling).unwrap();
+        let now = self.clock.now_s();
+        let res = sqlx::query!(
+            r#"
+            UPDATE decks
+            SET scheduling = ?, updated_at = ?
+            WHERE name = ?
+            "#,
+            scheduling,
+            now,
+            deck_name
+        )
+        .execute(&self.pool)
+        .await?;
+        if res.rows_affected() == 0 {
+            Err(RepositoryError::NotFound(
+                "deck".to_string(),
+                deck_name.to_string(),
+            ))?
+        }
+        Ok(())
+    }
+
+    pub async fn set_deck_plan(
+        &self,
+        deck_name: &str,
+        plan: Option<StudyPlan>,
+    ) -> Result<(), RepositoryError> {
+        let finish_by_s = plan.map(|plan| plan.finish_by_s);
+        let now = self.clock.now_s();
+        let res = sqlx::query!(
+            r#"
+            UPDATE decks
+            SET finish_by_s = ?, updated_at = ?
+            WHERE name = ?
+            "#,
+            finish_by_s,
+            now,
+            deck_name
+        )
+        .execute(&self.pool)
+        .await?;
+        if res.rows_affected() == 0 {
+            Err(RepositoryError::NotFound(
+                "deck".to_string(),
+                deck_name.to_string(),
+            ))?
+        }
+        Ok(())
+    }
+
+    pub async fn get_deck_plan(
+        &self,
+        deck_name: &str,
+    ) -> Result<Option<StudyPlan>, RepositoryError> {
+        let finish_by_s = sqlx::query_scalar!(
+            r#"
+            SELECT finish_by_s
+            FROM decks
+            WHERE name = ?
+            "#,
+            deck_name
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RepositoryError::NotFound("deck".to_string(), deck_name.to_string()))?;
+        Ok(finish_by_s.map(|finish_by_s| StudyPlan { finish_by_s }))
+    }
+
     pub async fn get_deck(&self, name: &str) -> Result<Deck, RepositoryError> {
         // Fetch deck info
         let deck = sqlx::query!(
             r#"
-            SELECT name, description
+            SELECT name, description, scheduler as "scheduler: SchedulerKind", scheduling
             FROM decks
             WHERE name = ?
             "#,
@@ -79,15 +247,18 @@ impl Repository {
             SELECT
                 id,
                 ord,
+                key,
                 expected_output,
                 expected_input,
                 command,
                 docker_image,
                 work_dir,
                 volume_mounts,
-                one_time
+                one_time,
+                tags,
+                files
             FROM cards
-            WHERE deck_name = ?
+            WHERE deck_name = ? AND NOT retired
             ORDER BY ord
             "#,
             name
@@ -97,25 +268,81 @@ impl Repository {
 
         let mut cards = Vec::new();
         for row in card_rows {
-            let mounts: Vec<(String, String)> =
-                serde_json::from_str(&row.volume_mounts).unwrap_or_default();
+            let mounts: Vec<(String, String)> = serde_json::from_str(&row.volume_mounts)?;
 
             cards.push(Card {
                 id: row.id.unwrap_or(0),
+                key: row.key,
                 expected_output: row.expected_output,
                 expected_input: row.expected_input,
                 command: row.command,
                 docker_image: row.docker_image,
                 work_dir: row.work_dir,
                 volume_mounts: mounts,
-                one_time: row.one_time
+                one_time: row.one_time,
+                tags: serde_json::from_str(&row.tags)?,
+                files: serde_json::from_str(&row.files)?,
             });
         }
 
         Ok(Deck {
             name: deck.name,
             description: deck.description,
+            scheduler: deck.scheduler,
+            scheduling: serde_json::from_str(&deck.scheduling)?,
             cards,
         })
     }
 }
+
+async fn insert_card(
+    tx: &mut Transaction<'_, Sqlite>,
+    deck_name: &str,
+    ord: i64,
+    card: &Card,
+) -> Result<i64, RepositoryError> {
+    sqlx::query_scalar(r#"
+        INSERT INTO cards
+            (deck_name, ord, key, expected_output, expected_input, command, docker_image, work_dir, volume_mounts, one_time, tags, files)
+        VALUES
+            (?,         ?,   ?,   ?,               ?,              ?,       ?,            ?,        ?,             ?,        ?,    ?)
+        RETURNING id
+        "#)
+        .bind(deck_name)
+        .bind(ord)
+        .bind(&card.key)
+        .bind(&card.expected_output)
+        .bind(&card.expected_input)
+        .bind(&card.command)
+        .bind(&card.docker_image)
+        .bind(&card.work_dir)
+        .bind(serde_json::to_string(&card.volume_mounts).unwrap())
+        .bind(card.one_time)
+        .bind(serde_json::to_string(&card.tags).unwrap())
+        .bind(serde_json::to_string(&card.files).unwrap())
+        .fetch_one(&mut **tx)
+        .await
+        .map_err(|e| e.into())
+}
+
+async fn insert_card_state(
+    tx: &mut Transaction<'_, Sqlite>,
+    card_id: i64,
+    starting_ease: i64,
+    now_s: i64,
+) -> Result<(), RepositoryError> {
+    sqlx::query(
+        r#"
+        INSERT INTO card_state
+            (card_id, ease, next_review_s)
+        VALUES
+            (?,       ?,    ?)
+        "#,
+    )
+    .bind(card_id)
+    .bind(starting_ease)
+    .bind(now_s)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
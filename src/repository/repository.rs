@@ -1,14 +1,16 @@
 use std::io;
 
-use directories::ProjectDirs;
 use sqlx::{
     Pool, SqlitePool,
     migrate::{MigrateDatabase, MigrateError},
 };
 use thiserror::Error;
 
+use crate::utils::{clock::Clock, data_dir::data_dir};
+
 pub struct Repository {
     pub(in crate::repository) pool: Pool<sqlx::Sqlite>,
+    pub(in crate::repository) clock: Clock,
 }
 
 #[derive(Debug, Error)]
@@ -33,14 +35,14 @@ pub enum RepositoryError {
 
     #[error("not found: {0} {1}")]
     NotFound(String, String),
+
+    #[error("corrupt stored data: {0}")]
+    Corrupt(#[from] serde_json::Error),
 }
 
 impl Repository {
-    pub async fn new() -> Result<Repository, CreateRepositoryError> {
-        let mut path = ProjectDirs::from("com", "ficolas2", "termcards")
-            .ok_or_else(|| CreateRepositoryError::NoHomeDir)?
-            .data_local_dir()
-            .to_path_buf();
+    pub async fn new(clock: Clock) -> Result<Repository, CreateRepositoryError> {
+        let mut path = data_dir().ok_or_else(|| CreateRepositoryError::NoHomeDir)?;
         path.push("termcards.sqlite");
 
         if let Some(parent) = path.parent() {
@@ -58,6 +60,21 @@ impl Repository {
 
         sqlx::migrate!("./migrations").run(&pool).await?;
 
-        Ok(Repository { pool })
+        Ok(Repository { pool, clock })
+    }
+
+    /// A repository on a fresh database in memory, for tests
+    #[cfg(test)]
+    pub async fn in_memory(clock: Clock) -> Repository {
+        // Every connection to `sqlite::memory:` opens its own database, so keep exactly one
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        Repository { pool, clock }
     }
 }
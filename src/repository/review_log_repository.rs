@@ -0,0 +1,313 @@
+use crate::domain::{
+    card_state::{CardState, ReviewResult},
+    optimizer::ReviewEvent,
+    review_log::{RATING_FORGET, ReviewLog},
+};
+
+use super::repository::{Repository, RepositoryError};
+
+impl Repository {
+    /// Review log of a deck, or of one card of it, oldest first, paired with the position of the
+    /// card in the deck.
+    pub async fn get_review_history(
+        &self,
+        deck_name: &str,
+        card_id: Option<i64>,
+    ) -> Result<Vec<(i64, ReviewLog)>, RepositoryError> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT
+                    cards.ord,
+                    review_log.id          as "id!: i64",
+                    review_log.card_id,
+                    review_log.timestamp,
+                    review_log.rating,
+                    review_log.prev_ivl,
+                    review_log.new_ivl,
+                    review_log.prev_ease,
+                    review_log.new_ease,
+                    review_log.duration_ms,
+                    review_log.commands,
+                    review_log.revealed,
+                    review_log.prev_status
+                FROM review_log
+                INNER JOIN cards ON cards.id = review_log.card_id
+                WHERE
+                    cards.deck_name = ?
+                    AND (? IS NULL OR cards.id = ?)
+                ORDER BY review_log.timestamp, review_log.id
+            "#,
+            deck_name,
+            card_id,
+            card_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.ord,
+                    ReviewLog {
+                        id: row.id,
+                        card_id: row.card_id,
+                        timestamp: row.timestamp,
+                        rating: row.rating,
+                        prev_ivl: row.prev_ivl,
+                        new_ivl: row.new_ivl,
+                        prev_ease: row.prev_ease,
+                        new_ease: row.new_ease,
+                        duration_ms: row.duration_ms,
+                        commands: row.commands,
+                        revealed: row.revealed,
+                        prev_status: row.prev_status.into(),
+                    },
+                )
+            })
+            .collect())
+    }
+
+    /// Most recent review log entry, with the card state it replaced, together with the other
+    /// entries of its batch, newest first. The snapshot is missing for entries logged before undo
+    /// was supported.
+    pub async fn get_last_reviews(
+        &self,
+    ) -> Result<Vec<(ReviewLog, Option<CardState>)>, RepositoryError> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT
+                    id as "id!: i64",
+                    card_id,
+                    timestamp,
+                    rating,
+                    prev_ivl,
+                    new_ivl,
+                    prev_ease,
+                    new_ease,
+                    duration_ms,
+                    commands,
+                    revealed,
+                    prev_status,
+                    prev_state
+                FROM review_log
+                WHERE id = (SELECT MAX(id) FROM review_log)
+                    OR batch_id = (SELECT batch_id FROM review_log ORDER BY id DESC LIMIT 1)
+                ORDER BY id DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok((
+                    ReviewLog {
+                        id: row.id,
+                        card_id: row.card_id,
+                        timestamp: row.timestamp,
+                        rating: row.rating,
+                        prev_ivl: row.prev_ivl,
+                        new_ivl: row.new_ivl,
+                        prev_ease: row.prev_ease,
+                        new_ease: row.new_ease,
+                        duration_ms: row.duration_ms,
+                        commands: row.commands,
+                        revealed: row.revealed,
+                        prev_status: row.prev_status.into(),
+                    },
+                    row.prev_state
+                        .map(|prev_state| serde_json::from_str(&prev_state))
+                        .transpose()?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Ratings of every repeating card of a deck, grouped by card and oldest first
+    pub async fn get_review_events(
+        &self,
+        deck_name: &str,
+    ) -> Result<Vec<Vec<ReviewEvent>>, RepositoryError> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT
+                    review_log.card_id,
+                    review_log.timestamp,
+                    review_log.rating
+                FROM review_log
+                INNER JOIN cards ON cards.id = review_log.card_id
+                WHERE
+                    cards.deck_name = ?
+                    AND NOT cards.one_time
+                ORDER BY review_log.card_id, review_log.timestamp, review_log.id
+            "#,
+            deck_name
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut histories: Vec<Vec<ReviewEvent>> = Vec::new();
+        let mut last_card_id = None;
+        for row in rows {
+            if last_card_id != Some(row.card_id) {
+                histories.push(Vec::new());
+                last_card_id = Some(row.card_id);
+            }
+            let history = histories.last_mut().unwrap();
+            match ReviewResult::from_rating(row.rating) {
+                Some(result) => history.push(ReviewEvent {
+                    timestamp: row.timestamp,
+                    result,
+                }),
+                // A forgotten card is learned again from scratch
+                None if row.rating == RATING_FORGET => history.clear(),
+                None => {}
+            }
+        }
+        Ok(histories)
+    }
+
+    /// Number of new cards introduced and review cards answered in a deck since `since_s`
+    pub async fn count_studied_since(
+        &self,
+        deck_name: &str,
+        since_s: i64,
+    ) -> Result<(i64, i64), RepositoryError> {
+        let res = sqlx::query!(
+            r#"
+                SELECT
+                    COUNT(DISTINCT CASE WHEN review_log.prev_status = 0 THEN review_log.card_id END) as "new!: i64",
+                    COUNT(CASE WHEN review_log.prev_status = 2 THEN 1 END)                         as "reviews!: i64"
+                FROM review_log
+                INNER JOIN cards ON cards.id = review_log.card_id
+                WHERE
+                    cards.deck_name = ?
+                    AND review_log.timestamp >= ?
+                    AND review_log.rating > 0
+            "#,
+            deck_name,
+            since_s
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((res.new, res.reviews))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        domain::{
+            card::Card,
+            card_state::{CardState, CardStatus},
+            deck::Deck,
+            review_log::{RATING_SET_DUE, ReviewLog},
+            test_utils::NOW,
+        },
+        repository::repository::{Repository, RepositoryError},
+        utils::clock::Clock,
+    };
+
+    /// A repository holding a deck of one card, and the id of the card
+    async fn repository_with_card() -> (Repository, i64) {
+        let repository = Repository::in_memory(Clock::starting_at(NOW)).await;
+        let deck = Deck {
+            name: "linux".to_string(),
+            description: String::new(),
+            scheduler: Default::default(),
+            scheduling: Default::default(),
+            cards: vec![Card::default()],
+        };
+        let deck = repository.save_deck(deck).await.unwrap();
+        (repository, deck.cards[0].id)
+    }
+
+    /// Saves a rating of the card, moving it to `status`
+    async fn save_rating(
+        repository: &Repository,
+        card_id: i64,
+        rating: i64,
+        timestamp: i64,
+        status: CardStatus,
+    ) {
+        let prev_state = repository.get_card_state(card_id).await.unwrap();
+        let card_state = CardState {
+            status,
+            ..prev_state.clone()
+        };
+        let review_log = ReviewLog {
+            id: 0,
+            card_id,
+            timestamp,
+            rating,
+            prev_ivl: card_state.interval_days,
+            new_ivl: card_state.interval_days,
+            prev_ease: card_state.ease,
+            new_ease: card_state.ease,
+            duration_ms: 0,
+            commands: 0,
+            revealed: false,
+            prev_status: prev_state.status,
+        };
+        repository
+            .save_review(&prev_state, card_state, review_log, false)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_last_reviews_snapshots() {
+        let (repository, card_id) = repository_with_card().await;
+        assert!(repository.get_last_reviews().await.unwrap().is_empty());
+        save_rating(&repository, card_id, 3, NOW, CardStatus::Learn).await;
+        let last_reviews = repository.get_last_reviews().await.unwrap();
+        assert_eq!(last_reviews.len(), 1);
+        assert_eq!(
+            last_reviews[0].1.as_ref().map(|cs| cs.status),
+            Some(CardStatus::New)
+        );
+
+        // Logged before undo was supported
+        sqlx::query!("UPDATE review_log SET prev_state = NULL")
+            .execute(&repository.pool)
+            .await
+            .unwrap();
+        let last_reviews = repository.get_last_reviews().await.unwrap();
+        assert!(last_reviews[0].1.is_none());
+
+        sqlx::query!("UPDATE review_log SET prev_state = '{'")
+            .execute(&repository.pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            repository.get_last_reviews().await,
+            Err(RepositoryError::Corrupt(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_count_studied_since() {
+        let (repository, card_id) = repository_with_card().await;
+        save_rating(&repository, card_id, 3, NOW, CardStatus::Learn).await;
+        save_rating(&repository, card_id, 1, NOW, CardStatus::Learn).await;
+        // Manual changes are not studying
+        save_rating(
+            &repository,
+            card_id,
+            RATING_SET_DUE,
+            NOW,
+            CardStatus::Review,
+        )
+        .await;
+        save_rating(&repository, card_id, 3, NOW, CardStatus::Review).await;
+
+        let counts = repository.count_studied_since("linux", NOW).await.unwrap();
+        assert_eq!(counts, (1, 1));
+        let counts = repository.count_studied_since("linux", NOW + 1).await;
+        assert_eq!(counts.unwrap(), (0, 0));
+        let counts = repository.count_studied_since("other", NOW).await;
+        assert_eq!(counts.unwrap(), (0, 0));
+    }
+}
@@ -1,52 +1,38 @@
-use crate::domain::{card::Card, card_state::CardState};
+use sqlx::{Sqlite, SqliteExecutor, Transaction};
 
-use super::repository::{Repository, RepositoryError};
+use crate::domain::{
+    card_state::{CardState, CardStatus},
+    review_log::ReviewLog,
+    scheduler::SchedulerKind,
+    scheduling::Scheduling,
+};
+
+use super::{
+    card_repository::suspend_card,
+    repository::{Repository, RepositoryError},
+};
 
 impl Repository {
-    pub async fn get_next_card_to_review(
+    pub async fn get_card_scheduling(
         &self,
-        prefix: &str,
-    ) -> Result<Option<Card>, RepositoryError> {
+        card_id: i64,
+    ) -> Result<(SchedulerKind, Scheduling), RepositoryError> {
         let res = sqlx::query!(
             r#"
-                SELECT 
-                    id,
-                    deck_name,
-                    expected_output,
-                    expected_input,
-                    command,
-                    docker_image,
-                    work_dir,
-                    volume_mounts,
-                    one_time,
-                    created_at,
-                    updated_at
+                SELECT
+                    decks.scheduler as "scheduler: SchedulerKind",
+                    decks.scheduling
                 FROM cards
-                JOIN card_state ON card_id = id
-                WHERE 
-                    deck_name LIKE ? || '%'
-                    AND next_review_s < strftime('%s', 'now')
-                    AND status >= 0
-                ORDER BY next_review_s, ord
+                JOIN decks ON decks.name = cards.deck_name
+                WHERE cards.id = ?
             "#,
-            prefix
+            card_id
         )
         .fetch_optional(&self.pool)
-        .await?;
-        // TODO: fetch learning first
-        // TODO: fetch learning even if time hasn't arrived yet
+        .await?
+        .ok_or_else(|| RepositoryError::NotFound("card".to_string(), card_id.to_string()))?;
 
-        Ok(res.map(|res| Card {
-            id: res.id,
-            volume_mounts: serde_json::from_str(&res.volume_mounts)
-                .expect("Invalid JSON in volume_mounts for card"),
-            expected_output: res.expected_output,
-            expected_input: res.expected_input,
-            command: res.command,
-            docker_image: res.docker_image,
-            work_dir: res.work_dir,
-            one_time: res.one_time,
-        }))
+        Ok((res.scheduler, serde_json::from_str(&res.scheduling)?))
     }
 
     pub async fn get_card_state(&self, id: i64) -> Result<CardState, RepositoryError> {
@@ -61,7 +47,11 @@ impl Repository {
                     reps,
                     lapses,
                     status,
-                    learning_step
+                    learning_step,
+                    stability,
+                    difficulty,
+                    leech,
+                    last_review_s
                 FROM card_state
                 WHERE 
                     card_id = ?
@@ -88,10 +78,14 @@ impl Repository {
                   cs.reps            as "reps!: i64",
                   cs.lapses          as "lapses!: i64",
                   cs.status          as "status!: i64",
-                  cs.learning_step
+                  cs.learning_step,
+                  cs.stability,
+                  cs.difficulty,
+                  cs.leech,
+                  cs.last_review_s
                 FROM card_state cs
                 INNER JOIN cards ON cards.id = cs.card_id
-                WHERE cards.deck_name = ?
+                WHERE cards.deck_name = ? AND NOT cards.retired
             "#,
             deck_name
         )
@@ -100,37 +94,201 @@ impl Repository {
         .map_err(|e| e.into())
     }
 
-    pub async fn set_card_state(&self, card_state: CardState) -> Result<(), RepositoryError> {
-        let res = sqlx::query!(
+    /// Due times in `[from_s, to_s)` of the other review cards in the deck of a card
+    pub async fn get_review_due_times(
+        &self,
+        card_id: i64,
+        from_s: i64,
+        to_s: i64,
+    ) -> Result<Vec<i64>, RepositoryError> {
+        sqlx::query_scalar!(
             r#"
-                UPDATE card_state
-                SET 
-                    next_review_s = ?,
-                    interval_days = ?,
-                    ease = ?,
-                    reps = ?,
-                    lapses = ?,
-                    status = ?,
-                    learning_step = ?
-                WHERE card_id = ?
+                SELECT cs.next_review_s
+                FROM card_state cs
+                INNER JOIN cards ON cards.id = cs.card_id
+                WHERE
+                    cards.deck_name = (SELECT deck_name FROM cards WHERE id = ?)
+                    AND NOT cards.retired
+                    AND cs.card_id != ?
+                    AND cs.status = 2
+                    AND cs.next_review_s >= ?
+                    AND cs.next_review_s < ?
             "#,
-            card_state.next_review_s,
-            card_state.interval_days,
-            card_state.ease,
-            card_state.reps,
-            card_state.lapses,
-            card_state.status,
-            card_state.learning_step,
-            card_state.card_id
+            card_id,
+            card_id,
+            from_s,
+            to_s
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.into())
+    }
+
+    /// Stores the new card state and appends its review log entry in a single transaction, along
+    /// with suspending the card when `suspend` is set. `prev_state` is kept with the log entry so
+    /// the review can be undone.
+    pub async fn save_review(
+        &self,
+        prev_state: &CardState,
+        card_state: CardState,
+        review_log: ReviewLog,
+        suspend: bool,
+    ) -> Result<(), RepositoryError> {
+        let mut tx: Transaction<'_, Sqlite> = self.pool.begin().await?;
+        insert_review(&mut tx, prev_state, &card_state, &review_log, None, suspend).await?;
+        if suspend {
+            suspend_card(&mut *tx, card_state.card_id).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Saves several reviews at once, as (previous state, new state, log entry), all or none.
+    /// The log entries share a batch id, so they are undone together.
+    pub async fn save_reviews(
+        &self,
+        reviews: Vec<(CardState, CardState, ReviewLog)>,
+    ) -> Result<(), RepositoryError> {
+        let mut tx: Transaction<'_, Sqlite> = self.pool.begin().await?;
+        let batch_id = sqlx::query_scalar!(
+            r#"SELECT COALESCE(MAX(batch_id), 0) + 1 as "batch_id!: i64" FROM review_log"#
         )
-        .execute(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
-        if res.rows_affected() == 0 {
-            Err(RepositoryError::NotFound(
-                "card_state".to_string(),
-                card_state.card_id.to_string(),
-            ))?
+        for (prev_state, card_state, review_log) in &reviews {
+            insert_review(
+                &mut tx,
+                prev_state,
+                card_state,
+                review_log,
+                Some(batch_id),
+                false,
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Restores the card states saved with review log entries, given as (entry id, state), and
+    /// removes the entries. Cards suspended or buried since stay so, and get the restored status
+    /// back when unsuspended, unless it was the review that suspended them as leeches.
+    pub async fn undo_reviews(
+        &self,
+        entries: Vec<(i64, CardState)>,
+    ) -> Result<(), RepositoryError> {
+        let mut tx: Transaction<'_, Sqlite> = self.pool.begin().await?;
+        for (review_log_id, prev_state) in &entries {
+            let current = sqlx::query!(
+                r#"
+                    SELECT card_state.status, review_log.suspended as "suspended: bool"
+                    FROM card_state, review_log
+                    WHERE card_state.card_id = ? AND review_log.id = ?
+                "#,
+                prev_state.card_id,
+                review_log_id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            update_card_state(&mut *tx, prev_state).await?;
+
+            let hidden = |status| matches!(status, CardStatus::Suspended | CardStatus::Buried);
+            if hidden(current.status.into()) && !current.suspended && !hidden(prev_state.status) {
+                sqlx::query!(
+                    "UPDATE card_state SET prev_status = status, status = ? WHERE card_id = ?",
+                    current.status,
+                    prev_state.card_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            sqlx::query!("DELETE FROM review_log WHERE id = ?", review_log_id)
+                .execute(&mut *tx)
+                .await?;
         }
+        tx.commit().await?;
         Ok(())
     }
 }
+
+async fn insert_review(
+    tx: &mut Transaction<'_, Sqlite>,
+    prev_state: &CardState,
+    card_state: &CardState,
+    review_log: &ReviewLog,
+    batch_id: Option<i64>,
+    suspended: bool,
+) -> Result<(), RepositoryError> {
+    update_card_state(&mut **tx, card_state).await?;
+    let prev_state = serde_json::to_string(prev_state).unwrap();
+    sqlx::query!(
+        r#"
+            INSERT INTO review_log
+                (card_id, timestamp, rating, prev_ivl, new_ivl, prev_ease, new_ease, duration_ms, commands, revealed, prev_status, prev_state, batch_id, suspended)
+            VALUES
+                (?,       ?,         ?,      ?,        ?,       ?,         ?,        ?,           ?,        ?,        ?,           ?,          ?,        ?)
+        "#,
+        review_log.card_id,
+        review_log.timestamp,
+        review_log.rating,
+        review_log.prev_ivl,
+        review_log.new_ivl,
+        review_log.prev_ease,
+        review_log.new_ease,
+        review_log.duration_ms,
+        review_log.commands,
+        review_log.revealed,
+        review_log.prev_status,
+        prev_state,
+        batch_id,
+        suspended,
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn update_card_state<'e, E: SqliteExecutor<'e>>(
+    executor: E,
+    card_state: &CardState,
+) -> Result<(), RepositoryError> {
+    let res = sqlx::query!(
+        r#"
+            UPDATE card_state
+            SET 
+                next_review_s = ?,
+                interval_days = ?,
+                ease = ?,
+                reps = ?,
+                lapses = ?,
+                status = ?,
+                learning_step = ?,
+                stability = ?,
+                difficulty = ?,
+                leech = ?,
+                last_review_s = ?
+            WHERE card_id = ?
+        "#,
+        card_state.next_review_s,
+        card_state.interval_days,
+        card_state.ease,
+        card_state.reps,
+        card_state.lapses,
+        card_state.status,
+        card_state.learning_step,
+        card_state.stability,
+        card_state.difficulty,
+        card_state.leech,
+        card_state.last_review_s,
+        card_state.card_id
+    )
+    .execute(executor)
+    .await?;
+    if res.rows_affected() == 0 {
+        Err(RepositoryError::NotFound(
+            "card_state".to_string(),
+            card_state.card_id.to_string(),
+        ))?
+    }
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use crate::{
+    domain::{
+        card::CardRef,
+        card_state::{CardState, CardStatus},
+        due_date::DueDate,
+        leech::Leech,
+        load_balance::spread,
+        review_log::{RATING_FORGET, RATING_POSTPONE, RATING_SET_DUE, ReviewLog},
+    },
+    repository::repository::RepositoryError,
+    utils::time_utils::{day_start_after_s, next_day_start_s},
+};
+
+use super::service::Service;
+
+impl Service {
+    pub async fn suspend_card(
+        &self,
+        deck_name: &str,
+        card: CardRef,
+    ) -> Result<(), RepositoryError> {
+        let card_id = self.repository.get_card_id(deck_name, &card).await?;
+        self.repository.suspend_card(card_id).await
+    }
+
+    pub async fn unsuspend_card(
+        &self,
+        deck_name: &str,
+        card: CardRef,
+    ) -> Result<(), RepositoryError> {
+        let card_id = self.repository.get_card_id(deck_name, &card).await?;
+        self.repository.unsuspend_card(card_id).await
+    }
+
+    pub async fn bury_card(&self, deck_name: &str, card: CardRef) -> Result<(), RepositoryError> {
+        let card_id = self.repository.get_card_id(deck_name, &card).await?;
+        self.bury(card_id).await
+    }
+
+    /// Resets a card to New, clearing its ease, reps and lapses
+    pub async fn forget_card(&self, deck_name: &str, card: CardRef) -> Result<(), RepositoryError> {
+        let card_id = self.repository.get_card_id(deck_name, &card).await?;
+        let (_, scheduling) = self.repository.get_card_scheduling(card_id).await?;
+        let now_s = self.clock.now_s();
+        self.edit_card_state(card_id, RATING_FORGET, |card_state| {
+            card_state.forget(scheduling.starting_ease, now_s)
+        })
+        .await
+    }
+
+    /// Moves the next review of a card to the start of the study day `due`
+    pub async fn set_card_due(
+        &self,
+        deck_name: &str,
+        card: CardRef,
+        due: DueDate,
+    ) -> Result<(), RepositoryError> {
+        let card_id = self.repository.get_card_id(deck_name, &card).await?;
+        let (_, scheduling) = self.repository.get_card_scheduling(card_id).await?;
+        let now_s = self.clock.now_s();
+        let due_s = due.day_start_s(now_s, scheduling.day_rollover_hour);
+        self.edit_card_state(card_id, RATING_SET_DUE, |card_state| {
+            card_state.set_due(due_s, now_s, scheduling.day_rollover_hour)
+        })
+        .await
+    }
+
+    /// Moves the review cards of every deck starting with `prefix` that are due in the next
+    /// `days` study days, backlog included, past them. They are spread over as many days after,
+    /// oldest first, so they do not all come back at once. Returns the cards moved per deck.
+    pub async fn postpone_reviews(
+        &self,
+        prefix: &str,
+        days: i64,
+    ) -> Result<Vec<(String, usize)>, RepositoryError> {
+        let deck_names = self.repository.get_deck_names(prefix).await?;
+        if deck_names.is_empty() {
+            Err(RepositoryError::NotFound(
+                "deck".to_string(),
+                prefix.to_string(),
+            ))?
+        }
+
+        let now_s = self.clock.now_s();
+        let mut postponed = vec![];
+        let mut reviews = vec![];
+        for deck_name in deck_names {
+            let deck = self.get_deck_queue(&deck_name).await?;
+            let rollover_hour = deck.scheduling.day_rollover_hour;
+            let return_s = day_start_after_s(now_s, days, rollover_hour);
+            let mut due: Vec<_> = deck
+                .cards
+                .iter()
+                .map(|(_, card_state)| card_state)
+                .filter(|cs| cs.status == CardStatus::Review && cs.next_review_s < return_s)
+                .collect();
+            due.sort_by_key(|cs| cs.next_review_s);
+
+            for (card_state, day) in due.iter().zip(spread(due.len(), days, days)) {
+                let due_s = day_start_after_s(now_s, day, rollover_hour);
+                reviews.push(edited_card_state(
+                    (*card_state).clone(),
+                    RATING_POSTPONE,
+                    now_s,
+                    |card_state| card_state.next_review_s = due_s,
+                ));
+            }
+            postponed.push((deck_name, due.len()));
+        }
+        // Saved together so they are undone together
+        self.repository.save_reviews(reviews).await?;
+        Ok(postponed)
+    }
+
+    pub async fn get_leeches(&self, prefix: &str) -> Result<Vec<Leech>, RepositoryError> {
+        self.repository.get_leeches(prefix).await
+    }
+
+    /// Hides a card until the next study day of its deck
+    pub(in crate::service) async fn bury(&self, card_id: i64) -> Result<(), RepositoryError> {
+        let (_, scheduling) = self.repository.get_card_scheduling(card_id).await?;
+        let until_s = next_day_start_s(self.clock.now_s(), scheduling.day_rollover_hour);
+        self.repository.bury_card(card_id, until_s).await
+    }
+
+    /// Applies a manual change to a card state, logged under `rating` so it shows in the history
+    /// and can be undone like a review
+    async fn edit_card_state(
+        &self,
+        card_id: i64,
+        rating: i64,
+        edit: impl FnOnce(&mut CardState),
+    ) -> Result<(), RepositoryError> {
+        let prev_state = self.repository.get_card_state(card_id).await?;
+        let (prev_state, card_state, review_log) =
+            edited_card_state(prev_state, rating, self.clock.now_s(), edit);
+        self.repository
+            .save_review(&prev_state, card_state, review_log, false)
+            .await
+    }
+}
+
+/// Applies a manual change to a card state, returning the previous state, the new one and
+/// the log entry to save them with
+fn edited_card_state(
+    prev_state: CardState,
+    rating: i64,
+    now_s: i64,
+    edit: impl FnOnce(&mut CardState),
+) -> (CardState, CardState, ReviewLog) {
+    let mut card_state = prev_state.clone();
+    edit(&mut card_state);
+
+    let review_log = ReviewLog {
+        id: 0,
+        card_id: prev_state.card_id,
+        timestamp: now_s,
+        rating,
+        prev_ivl: prev_state.interval_days,
+        new_ivl: card_state.interval_days,
+        prev_ease: prev_state.ease,
+        new_ease: card_state.ease,
+        duration_ms: 0,
+        commands: 0,
+        revealed: false,
+        prev_status: prev_state.status,
+    };
+    (prev_state, card_state, review_log)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        domain::{
+            card::CardRef,
+            card_state::{CardStatus, ReviewResult},
+        },
+        service::{
+            review_log_service::Undone,
+            service::Service,
+            test_utils::{card_state, import_deck, make_due, rate, service},
+        },
+    };
+
+    const DECKS: [&str; 2] = ["linux/files", "linux/processes"];
+    const KEYS: [&str; 2] = ["c1", "c2"];
+
+    async fn due_times(service: &Service) -> Vec<i64> {
+        let mut due = vec![];
+        for deck_name in DECKS {
+            for key in KEYS {
+                due.push(card_state(service, deck_name, key).await.next_review_s);
+            }
+        }
+        due
+    }
+
+    #[tokio::test]
+    async fn test_undo_postpone() {
+        let service = service().await;
+        for deck_name in DECKS {
+            import_deck(&service, deck_name, KEYS.len(), "").await;
+            for key in KEYS {
+                make_due(&service, deck_name, key).await;
+            }
+        }
+        let before = due_times(&service).await;
+
+        let postponed = service.postpone_reviews("linux/", 3).await.unwrap();
+        assert_eq!(
+            postponed,
+            vec![(DECKS[0].to_string(), 2), (DECKS[1].to_string(), 2)]
+        );
+        let first = due_times(&service).await;
+        service.postpone_reviews("linux/", 10).await.unwrap();
+        assert_ne!(due_times(&service).await, first);
+
+        // Each postpone is undone on its own, even within the same second
+        let undone = service.undo_last_review().await.unwrap();
+        assert!(matches!(undone, Some(Undone::Postpone { cards: 4 })));
+        assert_eq!(due_times(&service).await, first);
+        let undone = service.undo_last_review().await.unwrap();
+        assert!(matches!(undone, Some(Undone::Postpone { cards: 4 })));
+        assert_eq!(due_times(&service).await, before);
+
+        let undone = service.undo_last_review().await.unwrap();
+        assert!(matches!(undone, Some(Undone::Review { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_forget_keeps_suspension() {
+        let service = service().await;
+        import_deck(&service, "linux", 2, "").await;
+        for key in KEYS {
+            make_due(&service, "linux", key).await;
+            rate(&service, "linux", key, ReviewResult::Good).await;
+        }
+        let card = |key: &str| CardRef::Key(key.to_string());
+        service.suspend_card("linux", card("c1")).await.unwrap();
+        service.bury_card("linux", card("c2")).await.unwrap();
+
+        for (key, status) in KEYS
+            .into_iter()
+            .zip([CardStatus::Suspended, CardStatus::Buried])
+        {
+            service.forget_card("linux", card(key)).await.unwrap();
+            let forgotten = card_state(&service, "linux", key).await;
+            assert_eq!(forgotten.status, status);
+            assert_eq!(forgotten.reps, 0);
+        }
+        // Back as New rather than as the review cards they were
+        service.unsuspend_card("linux", card("c1")).await.unwrap();
+        let card_state = card_state(&service, "linux", "c1").await;
+        assert_eq!(card_state.status, CardStatus::New);
+    }
+}
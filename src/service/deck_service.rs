@@ -1,9 +1,23 @@
-use std::{fs, io, path::Path};
+use std::{collections::HashSet, fs, io, path::Path};
 use thiserror::Error;
 
 use crate::{
-    domain::{card::Card, card_state::CardState, deck::Deck},
+    domain::{
+        card::CardRef,
+        card_state::CardStatus,
+        deck::Deck,
+        deck_update::{UpdateSummary, match_cards},
+        due_date::DueDate,
+        package::PACKAGE_EXTENSION,
+        plan::StudyPlan,
+        review_queue::DeckQueue,
+    },
     repository::repository::RepositoryError,
+    utils::{
+        fixture_dir::is_contained,
+        path_utils::resolve_path,
+        time_utils::{day_start_s, format_date},
+    },
 };
 
 use super::service::Service;
@@ -18,29 +32,119 @@ pub enum CardImportError {
 
     #[error("repository error: {0}")]
     Repository(#[from] RepositoryError),
+
+    #[error("deck {0} is already imported, use --update to update it")]
+    DeckExists(String),
+
+    #[error("card id {0} is used more than once")]
+    DuplicateKey(String),
+
+    #[error("card id `{0}` is not valid, ids can't be empty or numbers, which refer to positions")]
+    InvalidKey(String),
+
+    #[error("invalid volume mount source: {0}")]
+    VolumePath(String),
+
+    #[error("volume mount source {0} does not exist")]
+    MissingVolume(String),
+
+    #[error("card {0} has files but no work_dir to mount them at")]
+    FilesWithoutWorkDir(usize),
+
+    #[error("card file {0} must be a relative path inside work_dir")]
+    FilePath(String),
+
+    #[error("card {0} has files and a volume mounted at its work_dir")]
+    FilesMountConflict(usize),
+
+    #[error("card file {0} has an invalid mode, it must be at most 0o7777")]
+    FileMode(String),
+
+    #[error("invalid package: {0}")]
+    Package(String),
+
+    #[error("invalid scheduling: {0}")]
+    Scheduling(String),
+}
+
+#[derive(Debug, Error)]
+pub enum StudyPlanError {
+    #[error("repository error: {0}")]
+    Repository(#[from] RepositoryError),
+
+    #[error("finish date {0} is in the past")]
+    PastDate(String),
 }
 
 impl Service {
     pub fn read_deck_from_file<P: AsRef<Path>>(&self, path: P) -> Result<Deck, CardImportError> {
-        let data = fs::read_to_string(path)?;
-        Ok(toml::from_str(&data)?)
+        let mut deck = parse_deck(&fs::read_to_string(&path)?)?;
+
+        // Mount sources are stored absolute, so the deck works wherever it was imported from
+        let base_dir = path.as_ref().parent().unwrap_or(Path::new(""));
+        for (host, _) in deck
+            .cards
+            .iter_mut()
+            .flat_map(|card| &mut card.volume_mounts)
+        {
+            let resolved = resolve_path(host, base_dir).map_err(CardImportError::VolumePath)?;
+            *host = fs::canonicalize(&resolved)
+                .map_err(|_| CardImportError::MissingVolume(resolved.display().to_string()))?
+                .display()
+                .to_string();
+        }
+        Ok(deck)
     }
 
-    pub async fn import_deck<P: AsRef<Path>>(&self, path: P) -> Result<Deck, CardImportError> {
+    /// Imports a deck file or package. With `update`, a deck already imported is updated instead, keeping
+    /// the progress of its cards, and the summary of the changes is returned.
+    pub async fn import_deck<P: AsRef<Path>>(
+        &self,
+        path: P,
+        update: bool,
+        retire: bool,
+    ) -> Result<Option<UpdateSummary>, CardImportError> {
+        let path = path.as_ref();
+        if path.extension().is_some_and(|ext| ext == PACKAGE_EXTENSION) {
+            return self.import_package(path, update, retire).await;
+        }
         let deck = self.read_deck_from_file(path)?;
-        let deck = self.repository.save_deck(deck).await?;
-
-        Ok(deck)
+        self.save_or_update_deck(deck, update, retire).await
     }
 
-    pub async fn get_deck_state(
+    /// Stores a new deck, or with `update` updates the stored deck of the same name
+    pub(in crate::service) async fn save_or_update_deck(
         &self,
-        deck_name: &str,
-    ) -> Result<Vec<(Card, CardState)>, RepositoryError> {
-        let deck = self.repository.get_deck(&deck_name).await?;
+        deck: Deck,
+        update: bool,
+        retire: bool,
+    ) -> Result<Option<UpdateSummary>, CardImportError> {
+        let stored = match self.repository.get_deck(&deck.name).await {
+            Ok(stored) => stored,
+            Err(RepositoryError::NotFound(..)) => {
+                self.repository.save_deck(deck).await?;
+                return Ok(None);
+            }
+            Err(e) => Err(e)?,
+        };
+        if !update {
+            Err(CardImportError::DeckExists(deck.name.clone()))?
+        }
+
+        let matches = match_cards(&stored.cards, &deck.cards);
+        let summary = matches.summary(&stored.cards, &deck.cards);
+        self.repository.update_deck(deck, &matches, retire).await?;
+        Ok(Some(summary))
+    }
+
+    pub async fn get_deck_queue(&self, deck_name: &str) -> Result<DeckQueue, RepositoryError> {
+        let now_s = self.clock.now_s();
+        self.repository.unbury_expired(now_s).await?;
+        let mut deck = self.repository.get_deck(deck_name).await?;
+        let plan = self.repository.get_deck_plan(deck_name).await?;
         let card_state = self.repository.get_deck_card_states(deck_name).await?;
 
-        Ok(deck
+        let cards: Vec<_> = deck
             .cards
             .iter()
             .map(|card| {
@@ -50,6 +154,173 @@ impl Service {
                     (*card_state.iter().find(|cs| cs.card_id == card_id).unwrap()).clone(),
                 )
             })
-            .collect())
+            .collect();
+
+        let day_start_s = day_start_s(now_s, deck.scheduling.day_rollover_hour);
+        let (new_studied, reviews_studied) = self
+            .repository
+            .count_studied_since(deck_name, day_start_s)
+            .await?;
+
+        if let Some(plan) = plan {
+            // Paced on the new cards left at the start of the day, so it holds until tomorrow
+            let new_cards = cards
+                .iter()
+                .filter(|(_, cs)| cs.status == CardStatus::New)
+                .count() as i64;
+            deck.scheduling.new_per_day = plan.new_per_day(
+                new_cards + new_studied,
+                now_s,
+                deck.scheduling.day_rollover_hour,
+            );
+        }
+
+        Ok(DeckQueue {
+            scheduler: deck.scheduler,
+            new_left: deck.scheduling.new_per_day - new_studied,
+            reviews_left: deck.scheduling.reviews_per_day - reviews_studied,
+            scheduling: deck.scheduling,
+            cards,
+            plan,
+        })
+    }
+
+    /// Sets the date every new card of the deck should be introduced by, or removes the plan
+    pub async fn set_study_plan(
+        &self,
+        deck_name: &str,
+        finish_by: Option<DueDate>,
+    ) -> Result<(), StudyPlanError> {
+        let deck = self.repository.get_deck(deck_name).await?;
+        let now_s = self.clock.now_s();
+        let rollover_hour = deck.scheduling.day_rollover_hour;
+        let plan = finish_by.map(|finish_by| StudyPlan {
+            finish_by_s: finish_by.day_start_s(now_s, rollover_hour),
+        });
+        if let Some(plan) = &plan
+            && plan.finish_by_s < day_start_s(now_s, rollover_hour)
+        {
+            Err(StudyPlanError::PastDate(format_date(plan.finish_by_s)))?
+        }
+        Ok(self.repository.set_deck_plan(deck_name, plan).await?)
+    }
+}
+
+/// Parses a deck file and checks the cards are consistent, leaving the mount sources as written
+pub(in crate::service) fn parse_deck(data: &str) -> Result<Deck, CardImportError> {
+    let deck: Deck = toml::from_str(data)?;
+    deck.scheduling
+        .validate(deck.scheduler)
+        .map_err(CardImportError::Scheduling)?;
+
+    let mut keys = HashSet::new();
+    for key in deck.cards.iter().filter_map(|card| card.key.as_ref()) {
+        if !matches!(key.parse(), Ok(CardRef::Key(_))) {
+            Err(CardImportError::InvalidKey(key.clone()))?
+        }
+        if !keys.insert(key) {
+            Err(CardImportError::DuplicateKey(key.clone()))?
+        }
+    }
+
+    for (ord, card) in deck.cards.iter().enumerate() {
+        if !card.files.is_empty() && card.work_dir.is_none() {
+            Err(CardImportError::FilesWithoutWorkDir(ord))?
+        }
+        if let Some(file) = card.files.iter().find(|file| !is_contained(&file.path)) {
+            Err(CardImportError::FilePath(file.path.clone()))?
+        }
+        if let Some(file) = card
+            .files
+            .iter()
+            .find(|file| file.mode.is_some_and(|mode| mode > 0o7777))
+        {
+            Err(CardImportError::FileMode(file.path.clone()))?
+        }
+        // The files are mounted at work_dir, which can't hold a second mount
+        if let Some(work_dir) = card.work_dir.as_ref().filter(|_| !card.files.is_empty())
+            && card
+                .volume_mounts
+                .iter()
+                .any(|(_, target)| Path::new(target) == Path::new(work_dir))
+        {
+            Err(CardImportError::FilesMountConflict(ord))?
+        }
+    }
+    Ok(deck)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        domain::due_date::DueDate,
+        service::test_utils::{import_deck, service},
+    };
+
+    use super::{CardImportError, StudyPlanError, parse_deck};
+
+    fn deck_file(keys: &[&str]) -> String {
+        let mut data = "name = \"linux\"\ndescription = \"\"\n".to_string();
+        for key in keys {
+            data.push_str(&format!(
+                "[[cards]]\nid = \"{key}\"\nvolume_mounts = []\nexpected_output = \"\"\n\
+                 expected_input = \"ls\"\ndocker_image = \"alpine\"\n"
+            ));
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_deck_keys() {
+        assert!(parse_deck(&deck_file(&["list", "c2"])).is_ok());
+        // Numbers would be read as positions
+        for key in ["3", "-1", ""] {
+            assert!(
+                matches!(
+                    parse_deck(&deck_file(&["list", key])),
+                    Err(CardImportError::InvalidKey(_))
+                ),
+                "{key}"
+            );
+        }
+        assert!(matches!(
+            parse_deck(&deck_file(&["list", "list"])),
+            Err(CardImportError::DuplicateKey(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_set_study_plan() {
+        let service = service().await;
+        import_deck(&service, "linux", 1, "").await;
+
+        // NOW is on 2023-11-14
+        let yesterday = DueDate::Date {
+            year: 2023,
+            month: 11,
+            day: 13,
+        };
+        let res = service.set_study_plan("linux", Some(yesterday)).await;
+        assert!(matches!(res, Err(StudyPlanError::PastDate(_))));
+        assert!(
+            service
+                .repository
+                .get_deck_plan("linux")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // Finishing today still works
+        let today = Some(DueDate::InDays(0));
+        service.set_study_plan("linux", today).await.unwrap();
+        assert!(
+            service
+                .repository
+                .get_deck_plan("linux")
+                .await
+                .unwrap()
+                .is_some()
+        );
     }
 }
@@ -0,0 +1,43 @@
+use crate::{
+    domain::forecast::{ForecastDay, forecast},
+    repository::repository::RepositoryError,
+    utils::time_utils::day_start_s,
+};
+
+use super::service::Service;
+
+impl Service {
+    /// Daily workload of every deck starting with `prefix`, for the next `days` study days
+    pub async fn get_forecast(
+        &self,
+        prefix: &str,
+        days: usize,
+    ) -> Result<Vec<ForecastDay>, RepositoryError> {
+        let deck_names = self.repository.get_deck_names(prefix).await?;
+        if deck_names.is_empty() {
+            Err(RepositoryError::NotFound(
+                "deck".to_string(),
+                prefix.to_string(),
+            ))?
+        }
+
+        // The study days of every deck fall on the same dates, whatever their rollover hour, so
+        // the totals keep the days of the first deck
+        let mut total: Vec<ForecastDay> = vec![];
+        for deck_name in deck_names {
+            let deck = self.get_deck_queue(&deck_name).await?;
+            let today_start_s = day_start_s(self.clock.now_s(), deck.scheduling.day_rollover_hour);
+            let deck_forecast = forecast(&deck, today_start_s, days);
+            if total.is_empty() {
+                total = deck_forecast;
+                continue;
+            }
+            for (total, day) in total.iter_mut().zip(deck_forecast) {
+                total.review += day.review;
+                total.learn += day.learn;
+                total.new += day.new;
+            }
+        }
+        Ok(total)
+    }
+}
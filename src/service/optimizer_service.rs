@@ -0,0 +1,41 @@
+use crate::{
+    domain::{
+        optimizer::{Fit, optimize},
+        scheduler::SchedulerKind,
+    },
+    repository::repository::RepositoryError,
+};
+
+use super::service::Service;
+
+impl Service {
+    /// Fits the scheduling parameters of every deck starting with `prefix` to its review history.
+    /// With `write`, the fitted parameters replace the deck scheduling settings.
+    pub async fn optimize_decks(
+        &self,
+        prefix: &str,
+        write: bool,
+    ) -> Result<Vec<(String, SchedulerKind, Option<Fit>)>, RepositoryError> {
+        let deck_names = self.repository.get_deck_names(prefix).await?;
+        if deck_names.is_empty() {
+            Err(RepositoryError::NotFound(
+                "deck".to_string(),
+                prefix.to_string(),
+            ))?
+        }
+
+        let mut fits = Vec::new();
+        for deck_name in deck_names {
+            let deck = self.repository.get_deck(&deck_name).await?;
+            let histories = self.repository.get_review_events(&deck_name).await?;
+            let fit = optimize(deck.scheduler, &deck.scheduling, &histories);
+            if write && let Some(fit) = &fit {
+                self.repository
+                    .set_deck_scheduling(&deck_name, &fit.scheduling)
+                    .await?;
+            }
+            fits.push((deck_name, deck.scheduler, fit));
+        }
+        Ok(fits)
+    }
+}
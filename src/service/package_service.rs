@@ -0,0 +1,402 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use tar::{Archive, Builder, EntryType};
+use thiserror::Error;
+
+use crate::{
+    domain::{
+        deck::Deck,
+        deck_update::UpdateSummary,
+        package::{PACKAGE_DECK_FILE, PACKAGE_DOCKERFILE, PACKAGE_EXTENSION, relocate_mounts},
+    },
+    repository::repository::RepositoryError,
+    utils::{data_dir::data_dir, fixture_dir::is_contained, temp_dir::TempDir},
+};
+
+use super::{
+    deck_service::{CardImportError, parse_deck},
+    service::Service,
+};
+
+/// Subdirectory of the data directory packages are unpacked into, one directory per deck
+const PACKAGES_DIR: &str = "decks";
+/// Name the replaced copy of a package takes while the new one is moved in
+const PACKAGE_DIR_BACKUP: &str = "deck";
+
+#[derive(Debug, Error)]
+pub enum PackageExportError {
+    #[error("failed to write package: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("failed to serialize deck: {0}")]
+    Toml(#[from] toml::ser::Error),
+
+    #[error("repository error: {0}")]
+    Repository(#[from] RepositoryError),
+}
+
+impl Service {
+    /// Builds a package of an imported deck, with the directories it mounts and the Dockerfile
+    /// it was installed with, if any. Returns the path the package was written to.
+    pub async fn export_package(
+        &self,
+        deck_name: &str,
+        output: Option<String>,
+    ) -> Result<PathBuf, PackageExportError> {
+        let deck = self.repository.get_deck(deck_name).await?;
+        let output = output.map_or_else(
+            || {
+                PathBuf::from(format!(
+                    "{}.{PACKAGE_EXTENSION}",
+                    deck_name.replace('/', "-")
+                ))
+            },
+            PathBuf::from,
+        );
+        let dockerfile = packages_dir()
+            .map(|dir| dir.join(deck_name).join(PACKAGE_DOCKERFILE))
+            .filter(|path| path.is_file());
+        write_package(deck, dockerfile.as_deref(), &output, self.clock.now_s())?;
+        Ok(output)
+    }
+
+    /// Imports a package, unpacking it into the data directory in place of the copy left by an
+    /// earlier import of the deck. The copy is only replaced once the deck is stored.
+    pub(in crate::service) async fn import_package(
+        &self,
+        path: &Path,
+        update: bool,
+        retire: bool,
+    ) -> Result<Option<UpdateSummary>, CardImportError> {
+        let packages_dir = packages_dir()
+            .ok_or_else(|| CardImportError::Package("home directory not found".to_string()))?;
+        fs::create_dir_all(&packages_dir)?;
+
+        let (staged, deck) = stage_package(path, &packages_dir)?;
+        let summary = self.save_or_update_deck(deck, update, retire).await?;
+        staged.install()?;
+        Ok(summary)
+    }
+}
+
+/// A package unpacked and checked next to where it gets installed
+struct StagedPackage {
+    dir: TempDir,
+    target: PathBuf,
+}
+
+impl StagedPackage {
+    /// Moves the unpacked package to its target, replacing the previous copy
+    fn install(self) -> io::Result<()> {
+        let parent = self.target.parent().unwrap_or(Path::new(""));
+        let previous = TempDir::create_in(parent, ".previous-")?;
+        let kept = previous.path.join(PACKAGE_DIR_BACKUP);
+        if self.target.exists() {
+            fs::rename(&self.target, &kept)?;
+        }
+        if let Err(e) = fs::rename(&self.dir.path, &self.target) {
+            if kept.exists() {
+                fs::rename(&kept, &self.target)?;
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+/// Writes a deck, the sources of its mounts and optionally a Dockerfile to a package at `output`
+fn write_package(
+    mut deck: Deck,
+    dockerfile: Option<&Path>,
+    output: &Path,
+    now_s: i64,
+) -> Result<(), PackageExportError> {
+    let dirs = relocate_mounts(&mut deck);
+    let encoder = GzEncoder::new(File::create(output)?, Compression::default());
+    let mut builder = Builder::new(encoder);
+    let deck_file = toml::to_string(&deck)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(deck_file.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(now_s as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, PACKAGE_DECK_FILE, deck_file.as_bytes())?;
+
+    for (source, packed) in dirs {
+        if Path::new(&source).is_dir() {
+            builder.append_dir_all(packed, source)?;
+        } else {
+            builder.append_path_with_name(source, packed)?;
+        }
+    }
+    if let Some(dockerfile) = dockerfile {
+        builder.append_path_with_name(dockerfile, PACKAGE_DOCKERFILE)?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Unpacks a package into a staging directory inside `packages_dir` and checks its deck, which is
+/// returned with the mounts pointing to where the package gets installed
+fn stage_package(
+    path: &Path,
+    packages_dir: &Path,
+) -> Result<(StagedPackage, Deck), CardImportError> {
+    let dir = TempDir::create_in(packages_dir, ".unpack-")?;
+    unpack_entries(path, &dir.path)?;
+
+    let deck_file = dir.path.join(PACKAGE_DECK_FILE);
+    if !deck_file.is_file() {
+        Err(CardImportError::Package(format!(
+            "{PACKAGE_DECK_FILE} not found"
+        )))?
+    }
+    let mut deck = parse_deck(&fs::read_to_string(&deck_file)?)?;
+    let target = packages_dir.join(&deck.name);
+    if !is_valid_deck_name(&deck.name) || target.parent() != Some(packages_dir) {
+        Err(CardImportError::Package(format!(
+            "invalid deck name {}",
+            deck.name
+        )))?
+    }
+    relocate_package_mounts(&mut deck, &dir.path, &target)?;
+    Ok((StagedPackage { dir, target }, deck))
+}
+
+/// Unpacks the regular files and directories of a package into `dir`, refusing links and
+/// entries leaving it
+fn unpack_entries(path: &Path, dir: &Path) -> Result<(), CardImportError> {
+    let mut archive = Archive::new(GzDecoder::new(File::open(path)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.display().to_string();
+        if !matches!(
+            entry.header().entry_type(),
+            EntryType::Regular | EntryType::Directory
+        ) {
+            Err(CardImportError::Package(format!(
+                "{entry_path} is not a regular file or directory"
+            )))?
+        }
+        if !is_contained(&entry_path) {
+            Err(CardImportError::Package(format!(
+                "{entry_path} is outside the package"
+            )))?
+        }
+        entry.unpack_in(dir)?;
+    }
+    Ok(())
+}
+
+/// Points the mounts of a packaged deck, which must be relative paths inside the package
+/// unpacked at `unpacked`, to where the package gets installed
+fn relocate_package_mounts(
+    deck: &mut Deck,
+    unpacked: &Path,
+    installed: &Path,
+) -> Result<(), CardImportError> {
+    let unpacked = fs::canonicalize(unpacked)?;
+    for (host, _) in deck
+        .cards
+        .iter_mut()
+        .flat_map(|card| &mut card.volume_mounts)
+    {
+        if !is_contained(host) {
+            Err(CardImportError::VolumePath(host.clone()))?
+        }
+        let source = fs::canonicalize(unpacked.join(&*host))
+            .map_err(|_| CardImportError::MissingVolume(host.clone()))?;
+        if !source.starts_with(&unpacked) {
+            Err(CardImportError::VolumePath(host.clone()))?
+        }
+        *host = installed.join(&*host).display().to_string();
+    }
+    Ok(())
+}
+
+/// Whether a packaged deck can be unpacked into a directory named after it: a single plain path
+/// component of letters, digits, `-`, `_` and `.`, not hidden
+fn is_valid_deck_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+}
+
+fn packages_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(PACKAGES_DIR))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, fs::File, path::Path};
+
+    use flate2::{Compression, write::GzEncoder};
+    use tar::{Builder, EntryType, Header};
+
+    use super::{
+        CardImportError, PACKAGE_DECK_FILE, is_valid_deck_name, stage_package, write_package,
+    };
+    use crate::{service::deck_service::parse_deck, utils::temp_dir::TempDir};
+
+    fn deck_file(name: &str, mount: &str) -> String {
+        format!(
+            r#"
+name = "{name}"
+description = ""
+
+[[cards]]
+volume_mounts = [["{mount}", "/data"]]
+expected_output = ""
+expected_input = "ls"
+docker_image = "alpine"
+"#
+        )
+    }
+
+    /// Writes a package of raw entries, as (path, type, data or link target)
+    fn write_raw_package(path: &Path, entries: &[(&str, EntryType, &str)]) {
+        let mut builder = Builder::new(GzEncoder::new(
+            File::create(path).unwrap(),
+            Compression::default(),
+        ));
+        for (name, entry_type, data) in entries {
+            let mut header = Header::new_gnu();
+            // Set the name directly, set_path refuses `..`
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(*entry_type);
+            header.set_mode(0o644);
+            match entry_type {
+                EntryType::Symlink => {
+                    header.set_link_name(data).unwrap();
+                    header.set_size(0);
+                }
+                _ => header.set_size(data.len() as u64),
+            }
+            header.set_cksum();
+            let data: &[u8] = match entry_type {
+                EntryType::Regular => data.as_bytes(),
+                _ => &[],
+            };
+            builder.append(&header, data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn stage_raw(entries: &[(&str, EntryType, &str)]) -> Result<(), CardImportError> {
+        let dir = TempDir::create_in(&std::env::temp_dir(), "termcards-test-").unwrap();
+        let path = dir.path.join("deck.tcdeck");
+        write_raw_package(&path, entries);
+        let packages_dir = dir.path.join("decks");
+        fs::create_dir(&packages_dir).unwrap();
+        stage_package(&path, &packages_dir).map(|_| ())
+    }
+
+    #[test]
+    fn test_package_round_trip() {
+        let dir = TempDir::create_in(&std::env::temp_dir(), "termcards-test-").unwrap();
+        let volume = dir.path.join("json");
+        fs::create_dir(&volume).unwrap();
+        fs::write(volume.join("login.json"), "{}").unwrap();
+        let dockerfile = dir.path.join("Dockerfile");
+        fs::write(&dockerfile, "FROM alpine").unwrap();
+
+        let deck = parse_deck(&deck_file("jq", &volume.display().to_string())).unwrap();
+        let package = dir.path.join("jq.tcdeck");
+        write_package(deck, Some(&dockerfile), &package, 0).unwrap();
+
+        let packages_dir = dir.path.join("decks");
+        fs::create_dir(&packages_dir).unwrap();
+        let target = packages_dir.join("jq");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("stale"), "").unwrap();
+
+        let (staged, deck) = stage_package(&package, &packages_dir).unwrap();
+        assert!(target.join("stale").exists());
+        staged.install().unwrap();
+
+        let (host, _) = &deck.cards[0].volume_mounts[0];
+        assert_eq!(host, &target.join("volumes/json").display().to_string());
+        assert_eq!(
+            fs::read_to_string(Path::new(host).join("login.json")).unwrap(),
+            "{}"
+        );
+        assert!(target.join("Dockerfile").is_file());
+        assert!(!target.join("stale").exists());
+        assert_eq!(fs::read_dir(&packages_dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_stage_package_refuses_traversal() {
+        let deck = deck_file("jq", "volumes/json");
+        assert!(matches!(
+            stage_raw(&[
+                (PACKAGE_DECK_FILE, EntryType::Regular, &deck),
+                ("../evil", EntryType::Regular, "x"),
+            ]),
+            Err(CardImportError::Package(_))
+        ));
+        for mount in ["../json", "/etc", "volumes/../../json"] {
+            let deck = deck_file("jq", mount);
+            assert!(
+                matches!(
+                    stage_raw(&[
+                        (PACKAGE_DECK_FILE, EntryType::Regular, &deck),
+                        ("volumes/json/", EntryType::Directory, ""),
+                    ]),
+                    Err(CardImportError::VolumePath(_))
+                ),
+                "{mount}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_stage_package_refuses_symlinks() {
+        let deck = deck_file("jq", "volumes/json");
+        assert!(matches!(
+            stage_raw(&[
+                (PACKAGE_DECK_FILE, EntryType::Regular, &deck),
+                ("volumes/", EntryType::Directory, ""),
+                ("volumes/json", EntryType::Symlink, "/etc"),
+            ]),
+            Err(CardImportError::Package(_))
+        ));
+    }
+
+    #[test]
+    fn test_stage_package_refuses_invalid_names() {
+        for name in ["/", "..", ".hidden", "a/b"] {
+            let deck = deck_file(name, "volumes/json");
+            assert!(
+                matches!(
+                    stage_raw(&[
+                        (PACKAGE_DECK_FILE, EntryType::Regular, &deck),
+                        ("volumes/json/", EntryType::Directory, ""),
+                    ]),
+                    Err(CardImportError::Package(_))
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_valid_deck_name() {
+        assert!(is_valid_deck_name("jq"));
+        assert!(is_valid_deck_name("git-basics_2.0"));
+        for name in [
+            "", "/", "..", ".", ".hidden", "a/b", "/etc", "a b", "~", "${HOME}",
+        ] {
+            assert!(!is_valid_deck_name(name), "{name}");
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{
+    domain::{
+        card::CardRef,
+        review_log::{RATING_POSTPONE, ReviewLog},
+    },
+    repository::repository::RepositoryError,
+};
+
+use super::service::Service;
+
+/// What `undo_last_review` reverted
+pub enum Undone {
+    /// A single rating, of the card at `ord` in its deck
+    Review {
+        deck_name: String,
+        ord: i64,
+        review_log: ReviewLog,
+    },
+    /// A postpone, as a whole
+    Postpone { cards: usize },
+}
+
+impl Service {
+    pub async fn get_review_history(
+        &self,
+        deck_name: &str,
+        card: Option<CardRef>,
+    ) -> Result<Vec<(i64, ReviewLog)>, RepositoryError> {
+        // Fail on unknown decks instead of printing an empty history
+        self.repository.get_deck(deck_name).await?;
+        let card_id = match card {
+            Some(card) => Some(self.repository.get_card_id(deck_name, &card).await?),
+            None => None,
+        };
+        self.repository.get_review_history(deck_name, card_id).await
+    }
+
+    /// Reverts the most recent rating, or the most recent postpone as a whole. Returns what was
+    /// undone, or `None` when there is nothing that can be undone.
+    pub async fn undo_last_review(&self) -> Result<Option<Undone>, RepositoryError> {
+        let mut last_reviews = self.repository.get_last_reviews().await?;
+        let Some(entries) = last_reviews
+            .iter()
+            .map(|(review_log, prev_state)| {
+                prev_state
+                    .clone()
+                    .map(|prev_state| (review_log.id, prev_state))
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Ok(None);
+        };
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        let cards = entries.len();
+        self.repository.undo_reviews(entries).await?;
+
+        let (review_log, _) = last_reviews.swap_remove(0);
+        if review_log.rating == RATING_POSTPONE {
+            return Ok(Some(Undone::Postpone { cards }));
+        }
+        let (deck_name, ord) = self
+            .repository
+            .get_card_position(review_log.card_id)
+            .await?;
+        Ok(Some(Undone::Review {
+            deck_name,
+            ord,
+            review_log,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        domain::{
+            card::CardRef,
+            card_state::{CardStatus, ReviewResult},
+        },
+        service::test_utils::{card_state, import_deck, make_due, rate, service},
+    };
+
+    use super::Undone;
+
+    #[tokio::test]
+    async fn test_undo_nothing() {
+        let service = service().await;
+        assert!(service.undo_last_review().await.unwrap().is_none());
+        import_deck(&service, "linux", 1, "").await;
+        rate(&service, "linux", "c1", ReviewResult::Good).await;
+        assert!(service.undo_last_review().await.unwrap().is_some());
+        assert!(service.undo_last_review().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_undo_keeps_later_suspension() {
+        let service = service().await;
+        import_deck(&service, "linux", 1, "").await;
+        make_due(&service, "linux", "c1").await;
+        rate(&service, "linux", "c1", ReviewResult::Good).await;
+        let reviewed = card_state(&service, "linux", "c1").await;
+        rate(&service, "linux", "c1", ReviewResult::Good).await;
+        let card = CardRef::Key("c1".to_string());
+        service.suspend_card("linux", card.clone()).await.unwrap();
+
+        let undone = service.undo_last_review().await.unwrap();
+        assert!(matches!(undone, Some(Undone::Review { .. })));
+        let undone_state = card_state(&service, "linux", "c1").await;
+        assert_eq!(undone_state.status, CardStatus::Suspended);
+        assert_eq!(undone_state.next_review_s, reviewed.next_review_s);
+
+        // Back to the state before the rating
+        service.unsuspend_card("linux", card).await.unwrap();
+        let card_state = card_state(&service, "linux", "c1").await;
+        assert_eq!(card_state.status, CardStatus::Review);
+        assert_eq!(card_state.reps, reviewed.reps);
+    }
+
+    #[tokio::test]
+    async fn test_undo_leech_suspension() {
+        let service = service().await;
+        import_deck(
+            &service,
+            "linux",
+            1,
+            "leech_threshold = 1\nleech_action = \"suspend\"",
+        )
+        .await;
+        make_due(&service, "linux", "c1").await;
+        rate(&service, "linux", "c1", ReviewResult::Again).await;
+        let leech = card_state(&service, "linux", "c1").await;
+        assert_eq!(leech.status, CardStatus::Suspended);
+        assert!(leech.leech);
+
+        // The review suspended it, so undoing the review unsuspends it
+        service.undo_last_review().await.unwrap();
+        let card_state = card_state(&service, "linux", "c1").await;
+        assert_eq!(card_state.status, CardStatus::Review);
+        assert!(!card_state.leech);
+        assert_eq!(card_state.lapses, 0);
+    }
+}
@@ -1,6 +1,15 @@
 use crate::{
-    domain::{card::Card, card_state::ReviewResult, deck::Deck},
+    domain::{
+        attempt::Attempt,
+        card::Card,
+        card_state::ReviewResult,
+        cram::{CramCard, CramFilter},
+        deck::Deck,
+        review_queue,
+        scheduling::Scheduling,
+    },
     repository::repository::RepositoryError,
+    utils::fixture_dir::FixtureDir,
 };
 use atty::Stream;
 use crossterm::{
@@ -9,48 +18,176 @@ use crossterm::{
 };
 use libc::c_int;
 use portable_pty::{CommandBuilder, PtySize, native_pty_system};
+use rand::seq::SliceRandom;
 use std::{
+    collections::VecDeque,
     io::{self, Write},
     os::unix::io::AsRawFd,
+    time::Instant,
 };
+use thiserror::Error;
 
 use super::service::Service;
 
 const POLL_TIME_MS: c_int = 30;
+const CTRL_U: u8 = 0x15;
+
+#[derive(Debug, Error)]
+pub enum ReviewError {
+    #[error("repository error: {0}")]
+    Repository(#[from] RepositoryError),
+
+    #[error("failed to write the card files: {0}")]
+    Fixtures(#[from] io::Error),
+}
 
 impl Service {
-    pub async fn review(&self, deck_name: String) -> Result<(), RepositoryError> {
-        while let Some(card) = self.repository.get_next_card_to_review(&deck_name).await? {
-            let result = run_sandboxed_card(&card);
-            let mut card_state = self.repository.get_card_state(card.id).await?;
-            card_state.apply_review(result, card.one_time);
-            self.repository.set_card_state(card_state).await?;
+    /// Reviews the due cards of the decks starting with `deck_name`. With `ahead_days`, review
+    /// cards due within that many days are pulled forward.
+    pub async fn review(&self, deck_name: String, ahead_days: i64) -> Result<(), ReviewError> {
+        // Ratings given in this session that can still be undone from the review screen
+        let mut undoable = 0;
+        while let Some(card) = self.get_next_card_to_review(&deck_name, ahead_days).await? {
+            let (_, scheduling) = self.repository.get_card_scheduling(card.id).await?;
+            let mode = RunMode::Review {
+                can_undo: undoable > 0,
+            };
+            let run = run_sandboxed_card(&card, &scheduling, mode)?;
+            match run.action {
+                CardAction::Rate(result) => {
+                    self.schedule_card(&card, result, &run.attempt, ahead_days > 0)
+                        .await?;
+                    undoable += 1;
+                }
+                CardAction::Undo => {
+                    undoable = match self.undo_last_review().await? {
+                        Some(_) => undoable - 1,
+                        // The log holds nothing undoable anymore
+                        None => 0,
+                    };
+                }
+                CardAction::Suspend => self.repository.suspend_card(card.id).await?,
+                CardAction::Bury => self.bury(card.id).await?,
+            }
         }
 
         Ok(())
     }
 
-    pub async fn review_full_deck_by_name(&self, deck_name: String) -> Result<(), RepositoryError> {
-        let deck = self.repository.get_deck(&deck_name).await?;
-        self.review_full_deck(deck);
-        Ok(())
+    async fn get_next_card_to_review(
+        &self,
+        prefix: &str,
+        ahead_days: i64,
+    ) -> Result<Option<Card>, RepositoryError> {
+        let mut decks = Vec::new();
+        for deck_name in self.repository.get_deck_names(prefix).await? {
+            decks.push(self.get_deck_queue(&deck_name).await?);
+        }
+        Ok(review_queue::next_card(&decks, self.clock.now_s(), ahead_days).cloned())
     }
 
-    pub fn review_full_deck(&self, deck: Deck) {
-        for card in deck.cards {
-            run_sandboxed_card(&card);
+    /// Runs the cards of a deck matching `filter` without touching their state. With `requeue`,
+    /// failed cards come back at the end of the session until they pass.
+    pub async fn cram(
+        &self,
+        deck_name: &str,
+        filter: &CramFilter,
+        shuffle: bool,
+        requeue: bool,
+    ) -> Result<Vec<CramCard>, ReviewError> {
+        let deck_queue = self.get_deck_queue(deck_name).await?;
+        let cards = deck_queue
+            .cards
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (card, card_state))| filter.matches(card, card_state))
+            .map(|(ord, (card, _))| (ord as i64, card))
+            .collect();
+        Ok(cram_cards(cards, &deck_queue.scheduling, shuffle, requeue)?)
+    }
+
+    /// Runs every card of a deck that has not been imported
+    pub fn test_deck(&self, deck: Deck) -> Result<Vec<CramCard>, ReviewError> {
+        let cards = (0..).zip(deck.cards).collect();
+        Ok(cram_cards(cards, &deck.scheduling, false, false)?)
+    }
+}
+
+fn cram_cards(
+    mut cards: Vec<(i64, Card)>,
+    scheduling: &Scheduling,
+    shuffle: bool,
+    requeue: bool,
+) -> io::Result<Vec<CramCard>> {
+    if shuffle {
+        cards.shuffle(&mut rand::rng());
+    }
+    let mut results: Vec<CramCard> = cards
+        .iter()
+        .map(|(ord, card)| CramCard {
+            ord: *ord,
+            expected_input: card.expected_input.clone(),
+            failures: 0,
+        })
+        .collect();
+
+    let mut queue: VecDeque<usize> = (0..cards.len()).collect();
+    while let Some(i) = queue.pop_front() {
+        let run = run_sandboxed_card(&cards[i].1, scheduling, RunMode::Cram)?;
+        if run.attempt.revealed {
+            results[i].failures += 1;
+            if requeue {
+                queue.push_back(i);
+            }
         }
     }
+    Ok(results)
 }
 
-fn run_sandboxed_card(card: &Card) -> ReviewResult {
+/// What a card is run for
+#[derive(Clone, Copy)]
+enum RunMode {
+    /// Rated and scheduled. Undo is offered once a rating was given in the session.
+    Review { can_undo: bool },
+    /// Only checked, the card state is left alone
+    Cram,
+}
+
+enum CardAction {
+    Rate(ReviewResult),
+    Suspend,
+    Bury,
+    Undo,
+}
+
+struct CardRun {
+    action: CardAction,
+    attempt: Attempt,
+}
+
+fn run_sandboxed_card(card: &Card, scheduling: &Scheduling, mode: RunMode) -> io::Result<CardRun> {
     if !atty::is(Stream::Stdin) || !atty::is(Stream::Stdout) {
         eprintln!("TTY required");
         std::process::exit(2);
     }
 
+    // Removed once the card is done, after the container is gone
+    let fixture_dir = match card.files.is_empty() {
+        true => None,
+        false => Some(FixtureDir::create(&card.files)?),
+    };
+
+    let can_undo = matches!(mode, RunMode::Review { can_undo: true });
+    let cram = matches!(mode, RunMode::Cram);
     print!("\x1b[2J\x1b[H");
+    if can_undo {
+        println!("\x1b[2mCtrl-U: undo last rating\x1b[0m");
+    }
+    let started_at = Instant::now();
     let mut success = false;
+    let mut undo = false;
+    let mut typed = false;
+    let mut commands = 0;
     unsafe {
         let pty = native_pty_system();
         let pair = pty
@@ -71,6 +208,10 @@ fn run_sandboxed_card(card: &Card) -> ReviewResult {
             cmd.arg("-v");
             cmd.arg(format!("{host}:{cont}:ro"));
         }
+        if let (Some(dir), Some(work_dir)) = (&fixture_dir, &card.work_dir) {
+            cmd.arg("-v");
+            cmd.arg(format!("{}:{work_dir}:ro", dir.path().display()));
+        }
         if let Some(work_dir) = &card.work_dir {
             cmd.arg("-w");
             cmd.arg(work_dir);
@@ -144,52 +285,127 @@ fn run_sandboxed_card(card: &Card) -> ReviewResult {
             if fds[0].revents & libc::POLLIN != 0 {
                 let n = libc::read(stdin_fd, buf.as_mut_ptr() as *mut _, buf.len());
                 if n > 0 {
+                    // Ctrl-U before anything is typed undoes the last rating, there is no line
+                    // for the shell to kill yet
+                    if can_undo && !typed && n == 1 && buf[0] == CTRL_U {
+                        undo = true;
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        break;
+                    }
+                    typed = true;
+                    commands += buf[..n as usize].iter().filter(|&&b| b == b'\r').count() as i64;
                     let _ = libc::write(pty_fd, buf.as_ptr() as *const _, n as usize);
                 }
             }
         }
     }
     disable_raw_mode().unwrap();
-    let result = if success {
+    let attempt = Attempt {
+        duration_ms: started_at.elapsed().as_millis() as i64,
+        // Output can match while the solving line is still being echoed, before its Enter is read
+        commands: if success { commands.max(1) } else { commands },
+        revealed: !success,
+    };
+    if undo {
+        return Ok(CardRun {
+            action: CardAction::Undo,
+            attempt,
+        });
+    }
+    let suggested = attempt.suggested_rating(scheduling);
+    let hotkeys = "\x1b[2mSuspend (s)  /  Bury (b)\x1b[0m";
+    let continue_hint = "\x1b[2mPress any key to continue\x1b[0m";
+    if success {
         println!("\n\x1b[1;32mCorrect output!\x1b[0m\x1b[1;32m");
         println!("Expected input was: \x1b[0m{}", card.expected_input);
-        if card.one_time {
-            println!("\n\x1b[1;31mThis card will not repeat, as it is a learning card.\x1b[0m");
+        println!(
+            "\x1b[2mSolved in {:.1}s with {} command{}\x1b[0m",
+            attempt.duration_ms as f64 / 1000.0,
+            attempt.commands,
+            if attempt.commands == 1 { "" } else { "s" }
+        );
+        if cram {
+            println!("\n{continue_hint}");
+        } else if let Some(rating) = auto_rating(scheduling, &attempt, success) {
             println!(
-                "\n\x1b[1;31mAgain (1)\x1b[0m  /  \
-                \x1b[1;34mLearned (2-4)\x1b[0m\n"
+                "\n\x1b[1mRated {} automatically\x1b[0m",
+                rating_name(card, rating)
             );
+            return Ok(CardRun {
+                action: CardAction::Rate(rating),
+                attempt,
+            });
         } else {
-            println!(
-                "\n\x1b[1;31mAgain (1)\x1b[0m  /  \
-                \x1b[1;33mHard (2)\x1b[0m  /  \
-                \x1b[1;32mGood (3)\x1b[0m  /  \
-                \x1b[1;34mEasy (4)\x1b[0m\n"
-            );
-        }
-        enable_raw_mode().unwrap();
-        loop {
-            if let Event::Key(key_event) = event::read().unwrap() {
-                match key_event.code {
-                    KeyCode::Char('1') => break ReviewResult::Again,
-                    KeyCode::Char('2') => break ReviewResult::Hard,
-                    KeyCode::Char('3') => break ReviewResult::Good,
-                    KeyCode::Char('4') => break ReviewResult::Easy,
-                    _ => {}
-                }
-            }
+            print_rating_prompt(card, suggested);
+            println!("{hotkeys}");
         }
     } else {
         println!(
             "\n\x1b[1;31mCorrect answer was:\x1b[0m {}\n",
             card.expected_input
         );
-        event::read().unwrap();
-        ReviewResult::Again
+        println!("{}", if cram { continue_hint } else { hotkeys });
+    }
+
+    enable_raw_mode().unwrap();
+    let action = loop {
+        if let Event::Key(key_event) = event::read().unwrap() {
+            match key_event.code {
+                _ if cram => break CardAction::Rate(suggested),
+                KeyCode::Char('s') => break CardAction::Suspend,
+                KeyCode::Char('b') => break CardAction::Bury,
+                KeyCode::Char('1') if success => break CardAction::Rate(ReviewResult::Again),
+                KeyCode::Char('2') if success => break CardAction::Rate(ReviewResult::Hard),
+                KeyCode::Char('3') if success => break CardAction::Rate(ReviewResult::Good),
+                KeyCode::Char('4') if success => break CardAction::Rate(ReviewResult::Easy),
+                KeyCode::Enter => break CardAction::Rate(suggested),
+                // Without a choice to make, any key moves on
+                _ if !success => break CardAction::Rate(suggested),
+                _ => {}
+            }
+        }
     };
     disable_raw_mode().unwrap();
 
-    result
+    Ok(CardRun { action, attempt })
+}
+
+/// Rating applied without asking, the suggested one for cards solved with `auto_grade`. Failed
+/// cards still wait for a key, so their answer can be read.
+fn auto_rating(scheduling: &Scheduling, attempt: &Attempt, success: bool) -> Option<ReviewResult> {
+    (success && scheduling.auto_grade).then(|| attempt.suggested_rating(scheduling))
+}
+
+fn print_rating_prompt(card: &Card, suggested: ReviewResult) {
+    if card.one_time {
+        println!("\n\x1b[1;31mThis card will not repeat, as it is a learning card.\x1b[0m");
+        println!(
+            "\n\x1b[1;31mAgain (1)\x1b[0m  /  \
+            \x1b[1;34mLearned (2-4)\x1b[0m\n"
+        );
+    } else {
+        println!(
+            "\n\x1b[1;31mAgain (1)\x1b[0m  /  \
+            \x1b[1;33mHard (2)\x1b[0m  /  \
+            \x1b[1;32mGood (3)\x1b[0m  /  \
+            \x1b[1;34mEasy (4)\x1b[0m\n"
+        );
+    }
+    println!(
+        "Suggested: \x1b[1m{}\x1b[0m (Enter)",
+        rating_name(card, suggested)
+    );
+}
+
+fn rating_name(card: &Card, rating: ReviewResult) -> &'static str {
+    match rating {
+        _ if card.one_time => "Learned",
+        ReviewResult::Again => "Again",
+        ReviewResult::Hard => "Hard",
+        ReviewResult::Good => "Good",
+        ReviewResult::Easy => "Easy",
+    }
 }
 
 fn push_normalized(acc: &mut Vec<u8>, chunk: &[u8]) {
@@ -228,3 +444,29 @@ fn push_normalized(acc: &mut Vec<u8>, chunk: &[u8]) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::domain::{attempt::Attempt, card_state::ReviewResult, scheduling::Scheduling};
+
+    use super::auto_rating;
+
+    #[test]
+    fn test_auto_rating() {
+        let scheduling = Scheduling {
+            auto_grade: true,
+            ..Default::default()
+        };
+        let attempt = Attempt {
+            duration_ms: 30_000,
+            commands: 2,
+            revealed: false,
+        };
+        assert_eq!(
+            auto_rating(&scheduling, &attempt, true),
+            Some(ReviewResult::Good)
+        );
+        assert_eq!(auto_rating(&scheduling, &attempt, false), None);
+        assert_eq!(auto_rating(&Scheduling::default(), &attempt, true), None);
+    }
+}
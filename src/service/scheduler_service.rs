@@ -1,3 +1,131 @@
+use crate::{
+    domain::{
+        attempt::Attempt,
+        card::Card,
+        card_state::{CardState, CardStatus, ReviewResult},
+        load_balance::{balance, fuzz_range},
+        review_log::ReviewLog,
+        scheduling::{LeechAction, Scheduling},
+    },
+    repository::repository::RepositoryError,
+    utils::time_utils::{day_start_after_s, days_between},
+};
+
 use super::service::Service;
 
-impl Service {}
+impl Service {
+    /// Applies a rating to a card. With `ahead`, the card was served by a review-ahead session
+    /// and is scheduled as reviewed early when it was not due yet.
+    pub async fn schedule_card(
+        &self,
+        card: &Card,
+        result: ReviewResult,
+        attempt: &Attempt,
+        ahead: bool,
+    ) -> Result<(), RepositoryError> {
+        let (kind, scheduling) = self.repository.get_card_scheduling(card.id).await?;
+        let scheduler = scheduling.scheduler(kind);
+        let prev_state = self.repository.get_card_state(card.id).await?;
+        let mut card_state = prev_state.clone();
+        let now_s = self.clock.now_s();
+        if ahead && prev_state.next_review_s > now_s {
+            card_state.apply_early_review(scheduler.as_ref(), result, card.one_time, now_s);
+        } else {
+            card_state.apply_review(scheduler.as_ref(), result, card.one_time, now_s);
+        }
+        let became_leech = card_state.detect_leech(scheduling.leech_threshold);
+        if scheduling.fuzz && card_state.status == CardStatus::Review {
+            self.balance_due(&mut card_state, &scheduling, now_s)
+                .await?;
+        }
+
+        let review_log = ReviewLog {
+            id: 0,
+            card_id: card.id,
+            timestamp: now_s,
+            rating: result as i64,
+            prev_ivl: prev_state.interval_days,
+            new_ivl: card_state.interval_days,
+            prev_ease: prev_state.ease,
+            new_ease: card_state.ease,
+            duration_ms: attempt.duration_ms,
+            commands: attempt.commands,
+            revealed: attempt.revealed,
+            prev_status: prev_state.status,
+        };
+        let suspend = became_leech && scheduling.leech_action == LeechAction::Suspend;
+        self.repository
+            .save_review(&prev_state, card_state, review_log, suspend)
+            .await?;
+        Ok(())
+    }
+
+    /// Moves a review card to the quietest day within the fuzz range of its interval
+    async fn balance_due(
+        &self,
+        card_state: &mut CardState,
+        scheduling: &Scheduling,
+        now_s: i64,
+    ) -> Result<(), RepositoryError> {
+        let range = fuzz_range(card_state.interval_days, scheduling.max_interval_days);
+        if range.0 == range.1 {
+            return Ok(());
+        }
+
+        let rollover_hour = scheduling.day_rollover_hour;
+        let from_s = day_start_after_s(now_s, range.0, rollover_hour);
+        let to_s = day_start_after_s(now_s, range.1 + 1, rollover_hour);
+        let mut due_counts = vec![0; (range.1 - range.0 + 1) as usize];
+        for due_s in self
+            .repository
+            .get_review_due_times(card_state.card_id, from_s, to_s)
+            .await?
+        {
+            if let Some(count) =
+                due_counts.get_mut(days_between(from_s, due_s, rollover_hour) as usize)
+            {
+                *count += 1;
+            }
+        }
+
+        card_state.interval_days = balance(range, &due_counts, &mut rand::rng());
+        card_state.next_review_s =
+            day_start_after_s(now_s, card_state.interval_days, rollover_hour);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        domain::{
+            card::CardRef,
+            card_state::{CardStatus, ReviewResult},
+        },
+        service::test_utils::{card_state, import_deck, make_due, rate, service},
+    };
+
+    #[tokio::test]
+    async fn test_leech_action() {
+        let service = service().await;
+        import_deck(&service, "tagged", 1, "leech_threshold = 1").await;
+        let settings = "leech_threshold = 1\nleech_action = \"suspend\"";
+        import_deck(&service, "suspended", 1, settings).await;
+        for deck_name in ["tagged", "suspended"] {
+            make_due(&service, deck_name, "c1").await;
+            rate(&service, deck_name, "c1", ReviewResult::Again).await;
+            assert!(card_state(&service, deck_name, "c1").await.leech);
+        }
+
+        let tagged = card_state(&service, "tagged", "c1").await;
+        assert_eq!(tagged.status, CardStatus::Relearn);
+        let suspended = card_state(&service, "suspended", "c1").await;
+        assert_eq!(suspended.status, CardStatus::Suspended);
+
+        // Unsuspended, it goes on relearning
+        let card = CardRef::Key("c1".to_string());
+        service.unsuspend_card("suspended", card).await.unwrap();
+        let unsuspended = card_state(&service, "suspended", "c1").await;
+        assert_eq!(unsuspended.status, CardStatus::Relearn);
+    }
+}
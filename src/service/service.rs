@@ -1,11 +1,12 @@
-use crate::repository::repository::Repository;
+use crate::{repository::repository::Repository, utils::clock::Clock};
 
 pub struct Service {
     pub(in crate::service) repository: Repository,
+    pub(in crate::service) clock: Clock,
 }
 
 impl Service {
-    pub fn new(repository: Repository) -> Service {
-        Service { repository }
+    pub fn new(repository: Repository, clock: Clock) -> Service {
+        Service { repository, clock }
     }
 }
@@ -0,0 +1,76 @@
+use crate::{
+    domain::{
+        attempt::Attempt,
+        card::CardRef,
+        card_state::{CardState, ReviewResult},
+        due_date::DueDate,
+        test_utils::NOW,
+    },
+    repository::repository::Repository,
+    utils::clock::Clock,
+};
+
+use super::{deck_service::parse_deck, service::Service};
+
+/// A service on a fresh database in memory, with its clock started at `NOW`
+pub async fn service() -> Service {
+    let clock = Clock::starting_at(NOW);
+    Service::new(Repository::in_memory(clock).await, clock)
+}
+
+/// Imports a deck named `name` with cards of ids `c1` to `c{cards}`, and extra `settings` in its
+/// `[scheduling]` table
+pub async fn import_deck(service: &Service, name: &str, cards: usize, settings: &str) {
+    let mut data = format!("name = \"{name}\"\ndescription = \"\"\n\n[scheduling]\n{settings}\n");
+    for i in 1..=cards {
+        data.push_str(&format!(
+            r#"
+[[cards]]
+id = "c{i}"
+volume_mounts = []
+expected_output = ""
+expected_input = "echo {i}"
+docker_image = "alpine"
+"#
+        ));
+    }
+    let deck = parse_deck(&data).unwrap();
+    service
+        .save_or_update_deck(deck, false, false)
+        .await
+        .unwrap();
+}
+
+/// Stored state of the card with id `key`
+pub async fn card_state(service: &Service, deck_name: &str, key: &str) -> CardState {
+    let card = CardRef::Key(key.to_string());
+    let card_id = service
+        .repository
+        .get_card_id(deck_name, &card)
+        .await
+        .unwrap();
+    service.repository.get_card_state(card_id).await.unwrap()
+}
+
+/// Makes the card with id `key` a review card due today
+pub async fn make_due(service: &Service, deck_name: &str, key: &str) {
+    let card = CardRef::Key(key.to_string());
+    service
+        .set_card_due(deck_name, card, DueDate::InDays(0))
+        .await
+        .unwrap();
+}
+
+/// Rates the card with id `key` as if it was just studied
+pub async fn rate(service: &Service, deck_name: &str, key: &str, result: ReviewResult) {
+    let deck = service.repository.get_deck(deck_name).await.unwrap();
+    let card = deck
+        .cards
+        .iter()
+        .find(|card| card.key.as_deref() == Some(key))
+        .unwrap();
+    service
+        .schedule_card(card, result, &Attempt::default(), false)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,25 @@
+use super::time_utils;
+
+/// Source of the current time. Everything that needs "now" reads it from here, so the whole app
+/// can be moved in time with `--now` to simulate future sessions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Clock {
+    offset_s: i64,
+}
+
+impl Clock {
+    pub fn system() -> Clock {
+        Clock::default()
+    }
+
+    /// A clock that reads `now_s` at this instant and keeps running from there
+    pub fn starting_at(now_s: i64) -> Clock {
+        Clock {
+            offset_s: now_s - time_utils::now_s(),
+        }
+    }
+
+    pub fn now_s(&self) -> i64 {
+        time_utils::now_s() + self.offset_s
+    }
+}
@@ -0,0 +1,9 @@
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+
+/// Directory holding the database and the unpacked deck packages, None without a home directory
+pub fn data_dir() -> Option<PathBuf> {
+    ProjectDirs::from("com", "ficolas2", "termcards")
+        .map(|dirs| dirs.data_local_dir().to_path_buf())
+}
@@ -0,0 +1,78 @@
+use std::{
+    fs, io,
+    os::unix::fs::PermissionsExt,
+    path::{Component, Path},
+};
+
+use crate::domain::card::CardFile;
+
+use super::temp_dir::TempDir;
+
+const DEFAULT_MODE: u32 = 0o644;
+
+/// Temporary directory holding the fixture files of a card, removed when dropped
+pub struct FixtureDir {
+    dir: TempDir,
+}
+
+impl FixtureDir {
+    pub fn create(files: &[CardFile]) -> io::Result<FixtureDir> {
+        let dir = TempDir::create_in(&std::env::temp_dir(), "termcards-")?;
+        for file in files {
+            let file_path = dir.path.join(&file.path);
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&file_path, &file.content)?;
+            let mode = file.mode.unwrap_or(DEFAULT_MODE);
+            fs::set_permissions(&file_path, fs::Permissions::from_mode(mode))?;
+        }
+        Ok(FixtureDir { dir })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir.path
+    }
+}
+
+/// Whether a fixture path stays inside the directory it is written to
+pub fn is_contained(path: &str) -> bool {
+    let path = Path::new(path);
+    !path.as_os_str().is_empty() && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::domain::card::CardFile;
+
+    use super::{FixtureDir, is_contained};
+
+    #[test]
+    fn test_fixture_dir() {
+        let files = vec![CardFile {
+            path: "json/login.json".to_string(),
+            content: "{}".to_string(),
+            mode: None,
+        }];
+        let dir = FixtureDir::create(&files).unwrap();
+        let path = dir.path().to_path_buf();
+        assert_eq!(
+            fs::read_to_string(path.join("json/login.json")).unwrap(),
+            "{}"
+        );
+
+        drop(dir);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_is_contained() {
+        assert!(is_contained("login.json"));
+        assert!(is_contained("json/login.json"));
+        assert!(!is_contained("../login.json"));
+        assert!(!is_contained("/etc/passwd"));
+        assert!(!is_contained(""));
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+/// Expands a leading `~` and `${VAR}` references in `path`, taking their values from `var`
+pub fn expand_path(path: &str, var: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = path;
+    if rest == "~" || rest.starts_with("~/") {
+        expanded += &var("HOME").ok_or("HOME is not set")?;
+        rest = &rest[1..];
+    }
+
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed ${{ in {path}"))?;
+        let name = &rest[start + 2..start + end];
+        expanded += &rest[..start];
+        expanded += &var(name).ok_or_else(|| format!("{name} is not set"))?;
+        rest = &rest[start + end + 1..];
+    }
+    expanded += rest;
+    Ok(expanded)
+}
+
+/// Expands `path` with the environment and resolves it against `base_dir` when relative
+pub fn resolve_path(path: &str, base_dir: &Path) -> Result<PathBuf, String> {
+    let expanded = expand_path(path, |name| std::env::var(name).ok())?;
+    Ok(base_dir.join(expanded))
+}
+
+#[cfg(test)]
+mod test {
+    use super::expand_path;
+
+    #[test]
+    fn test_expand_path() {
+        let var = |name: &str| match name {
+            "HOME" => Some("/home/me".to_string()),
+            "DATA" => Some("/srv/data".to_string()),
+            _ => None,
+        };
+        assert_eq!(expand_path("~/json", var).unwrap(), "/home/me/json");
+        assert_eq!(expand_path("~", var).unwrap(), "/home/me");
+        assert_eq!(expand_path("a~/b", var).unwrap(), "a~/b");
+        assert_eq!(
+            expand_path("${DATA}/json/${DATA}", var).unwrap(),
+            "/srv/data/json//srv/data"
+        );
+        assert_eq!(expand_path("../volumes", var).unwrap(), "../volumes");
+        assert!(expand_path("${MISSING}/json", var).is_err());
+        assert!(expand_path("${DATA", var).is_err());
+    }
+}
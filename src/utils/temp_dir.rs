@@ -0,0 +1,55 @@
+use std::{
+    fs::{self, DirBuilder},
+    io,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+};
+
+const CREATE_ATTEMPTS: usize = 16;
+
+/// Directory with a random name, only created if it did not exist yet, and removed with its
+/// contents when dropped
+pub struct TempDir {
+    pub path: PathBuf,
+}
+
+impl TempDir {
+    pub fn create_in(parent: &Path, prefix: &str) -> io::Result<TempDir> {
+        for _ in 0..CREATE_ATTEMPTS {
+            let path = parent.join(format!("{prefix}{:016x}", rand::random::<u64>()));
+            match DirBuilder::new().mode(0o700).create(&path) {
+                Ok(()) => return Ok(TempDir { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "no free temporary directory name",
+        ))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TempDir;
+
+    #[test]
+    fn test_temp_dir() {
+        let parent = std::env::temp_dir();
+        let a = TempDir::create_in(&parent, "termcards-test-").unwrap();
+        let b = TempDir::create_in(&parent, "termcards-test-").unwrap();
+        assert_ne!(a.path, b.path);
+        assert!(a.path.is_dir());
+
+        let path = a.path.clone();
+        drop(a);
+        assert!(!path.exists());
+    }
+}
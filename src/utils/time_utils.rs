@@ -1,4 +1,10 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const MIN: i64 = 60;
+pub const DAY: i64 = 24 * 60 * MIN;
 
 pub fn now_s() -> i64 {
     SystemTime::now()
@@ -7,23 +13,144 @@ pub fn now_s() -> i64 {
         .as_secs() as i64
 }
 
-pub fn format_until_duration(diff: i64) -> String {
+/// Start of the study day containing `timestamp_s`. Study days begin at `rollover_hour` local
+/// time, so reviews done after midnight still count towards the previous day.
+pub fn day_start_s(timestamp_s: i64, rollover_hour: i64) -> i64 {
+    let time = (timestamp_s - rollover_hour * 60 * MIN) as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&time, &mut tm) };
+    tm.tm_hour = rollover_hour as libc::c_int;
+    tm.tm_min = 0;
+    tm.tm_sec = 0;
+    tm.tm_isdst = -1;
+    unsafe { libc::mktime(&mut tm) as i64 }
+}
+
+/// Start of the study day following the one containing `timestamp_s`
+pub fn next_day_start_s(timestamp_s: i64, rollover_hour: i64) -> i64 {
+    day_start_after_s(timestamp_s, 1, rollover_hour)
+}
+
+/// Start of the study day `days` after the one containing `timestamp_s`. Days are counted on the
+/// local calendar, so they may be shorter or longer than [`DAY`] around DST changes.
+pub fn day_start_after_s(timestamp_s: i64, days: i64, rollover_hour: i64) -> i64 {
+    let time = day_start_s(timestamp_s, rollover_hour) as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&time, &mut tm) };
+    tm.tm_mday += days as libc::c_int;
+    tm.tm_isdst = -1;
+    unsafe { libc::mktime(&mut tm) as i64 }
+}
+
+/// Start of the study day on the local calendar date `year-month-day`, or None if there is no
+/// such date
+pub fn date_start_s(year: i64, month: i64, day: i64, rollover_hour: i64) -> Option<i64> {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = (year - 1900) as libc::c_int;
+    tm.tm_mon = (month - 1) as libc::c_int;
+    tm.tm_mday = day as libc::c_int;
+    tm.tm_hour = rollover_hour as libc::c_int;
+    tm.tm_isdst = -1;
+    let time = unsafe { libc::mktime(&mut tm) as i64 };
+    // mktime normalizes out of range fields, like February 30th into March
+    let normalized = (
+        tm.tm_year as i64 + 1900,
+        tm.tm_mon as i64 + 1,
+        tm.tm_mday as i64,
+    );
+    (normalized == (year, month, day)).then_some(time)
+}
+
+/// Number of study days from the one containing `from_s` to the one containing `to_s`
+pub fn days_between(from_s: i64, to_s: i64, rollover_hour: i64) -> i64 {
+    let diff = day_start_s(to_s, rollover_hour) - day_start_s(from_s, rollover_hour);
+    (diff as f64 / DAY as f64).round() as i64
+}
+
+/// Uses the IANA timezone `name`, like `Europe/Madrid`, for local dates instead of the system one.
+/// Must be called while the process has a single thread.
+pub fn set_timezone(name: &str) -> Result<(), String> {
+    let tz_dir = std::env::var("TZDIR").unwrap_or_else(|_| "/usr/share/zoneinfo".to_string());
+    if name.is_empty() || name.contains("..") || !Path::new(&tz_dir).join(name).is_file() {
+        return Err(format!("unknown timezone: {name}"));
+    }
+    // SAFETY: only called from `main` before the runtime starts, so no other thread can be
+    // reading the environment
+    unsafe {
+        std::env::set_var("TZ", name);
+        tzset();
+    }
+    Ok(())
+}
+
+unsafe extern "C" {
+    fn tzset();
+}
+
+/// Formats a unix timestamp as local `YYYY-MM-DD`
+pub fn format_date(timestamp_s: i64) -> String {
+    format_timestamp(timestamp_s)[..10].to_string()
+}
+
+/// Formats a unix timestamp as local `YYYY-MM-DD HH:MM`
+pub fn format_timestamp(timestamp_s: i64) -> String {
+    let time = timestamp_s as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&time, &mut tm) };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min
+    )
+}
+
+/// Describes how long until `due_s`. Anything due on a later study day is counted in days, so a
+/// card due at the next rollover reads "tomorrow" rather than "in 5 hours".
+pub fn format_until_duration(due_s: i64, now_s: i64, rollover_hour: i64) -> String {
+    let diff = due_s - now_s;
     if diff <= 0 {
         return "now".to_string();
     }
 
+    let days = days_between(now_s, due_s, rollover_hour);
     let minutes = ((diff as f64) / 60.0).round() as i64;
     let hours = ((diff as f64) / 3600.0).round() as i64;
-    let days = ((diff as f64) / 86_400.0).round() as i64;
 
-    if days > 0 {
-        format!("in {} day{}", days, if days == 1 { "" } else { "s" })
+    if days == 1 {
+        "tomorrow".to_string()
+    } else if days > 1 {
+        format!("in {days} days")
     } else if hours > 0 {
         format!("in {} hour{}", hours, if hours == 1 { "" } else { "s" })
     } else if minutes > 0 {
-        format!("in {} minute{}", minutes, if minutes == 1 { "" } else { "s" })
+        format!(
+            "in {} minute{}",
+            minutes,
+            if minutes == 1 { "" } else { "s" }
+        )
     } else {
         format!("in {} second{}", diff, if diff == 1 { "" } else { "s" })
     }
 }
 
+#[cfg(test)]
+mod test {
+    use crate::domain::test_utils::NOW;
+
+    use super::{DAY, day_start_after_s, format_until_duration};
+
+    #[test]
+    fn test_format_until_duration_counts_study_days() {
+        let tomorrow = day_start_after_s(NOW, 1, 4);
+        assert_eq!(format_until_duration(NOW - 1, NOW, 4), "now");
+        assert_eq!(format_until_duration(NOW + 90, NOW, 4), "in 2 minutes");
+        assert_eq!(format_until_duration(tomorrow, NOW, 4), "tomorrow");
+        assert_eq!(
+            format_until_duration(tomorrow + 2 * DAY, NOW, 4),
+            "in 3 days"
+        );
+    }
+}